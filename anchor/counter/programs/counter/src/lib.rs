@@ -2,8 +2,8 @@ use anchor_lang::prelude::*;
 
 declare_id!("hbJ8Kmhb8EZ2nHZ7nFhjDnKaAcWATycCmHr9WY4DaEo");
 
-mod instructions;
-mod state;
+pub mod instructions;
+pub mod state;
 
 use instructions::*;
 
@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum EscrowError {
+    #[msg("Deposit amount must be greater than zero")]
+    InvalidDepositAmount,
+    #[msg("Receive amount must be greater than zero")]
+    InvalidReceiveAmount,
+    #[msg("Minimum fill amount must be between one and the receive amount")]
+    InvalidMinFillAmount,
+    #[msg("Fill amount must be greater than zero")]
+    InvalidFillAmount,
+    #[msg("Fill amount exceeds the remaining receive amount")]
+    FillExceedsRemaining,
+    #[msg("Fill amount is below the offer's minimum fill size")]
+    FillBelowMinimum,
+    #[msg("Fill amount is too small to pay out any tokens")]
+    FillTooSmall,
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Offer has expired")]
+    OfferExpired,
+    #[msg("Offer has not expired yet")]
+    OfferNotExpired,
+    #[msg("Offer is reserved for a different taker")]
+    UnauthorizedTaker,
+    #[msg("Mint has a Token-2022 extension the escrow does not support")]
+    UnsupportedMintExtension,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Fee exceeds the maximum allowed basis points")]
+    InvalidFee,
+    #[msg("Accounts do not match the offer's mints, token accounts or native SOL legs")]
+    InvalidTokenAccounts,
+    #[msg("Basket legs must be non-empty, within the maximum and use distinct mints")]
+    InvalidBasket,
+    #[msg("Mint needs zero decimals, a supply of one and no mint authority")]
+    InvalidNft,
+    #[msg("NFT amounts must be exactly one")]
+    InvalidNftAmount,
+    #[msg("NFT does not belong to the expected collection")]
+    CollectionMismatch,
+    #[msg("Auction must start at the receive amount and decline to a positive amount over a non-empty window")]
+    InvalidAuction,
+    #[msg("Auction offers cannot be updated")]
+    AuctionNotUpdatable,
+    #[msg("Preimage does not match the offer's hashlock")]
+    InvalidPreimage,
+    #[msg("Hash-timelocked offers are locked until their timeout")]
+    HashlockActive,
+    #[msg("Order is not signed by its maker")]
+    InvalidSignature,
+    #[msg("Arbiter must differ from the payer and payee, who must differ from each other")]
+    InvalidArbiter,
+    #[msg("Arbitration has not been disputed")]
+    NotDisputed,
+    #[msg("Payee share must be a percentage between 0 and 100")]
+    InvalidSplit,
+    #[msg("Milestones must be non-empty, within the maximum and have positive amounts")]
+    InvalidMilestones,
+    #[msg("Milestone has already been released")]
+    MilestoneReleased,
+    #[msg("Milestone is not unlocked yet")]
+    MilestoneLocked,
+    #[msg("Offers must be on opposite sides of the same pair at crossing prices")]
+    OffersDoNotCross,
+    #[msg("Offers with a receipt are settled by redeeming the receipt")]
+    ReceiptRequired,
+    #[msg("Offers cannot fill the requested amount within max_pay")]
+    FillNotAchievable,
+    #[msg("Hash-timelocked offers must name their taker")]
+    HashlockWithoutTaker,
+    #[msg("Offer is already listed or has been filled")]
+    NotListable,
+    #[msg("Market has no room for another offer")]
+    MarketFull,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+}
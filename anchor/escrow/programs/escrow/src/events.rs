@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+
+/// Protocol fees paid to the treasury for a fill. A taker pays only `fee_y`.
+#[event]
+pub struct FeesCollected {
+    pub escrow: Pubkey,
+    pub treasury: Pubkey,
+    pub mint_x: Pubkey,
+    pub fee_x: u64,
+    pub mint_y: Pubkey,
+    pub fee_y: u64,
+}
+
+#[event]
+pub struct OfferMade {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Option<Pubkey>,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub deposit_amount: u64,
+    pub receive_amount: u64,
+}
+
+/// `fill_amount` of `mint_y` was paid for `payout` of `mint_x`, both before
+/// protocol fees.
+#[event]
+pub struct OfferTaken {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub fill_amount: u64,
+    pub payout: u64,
+    pub remaining_deposit: u64,
+    pub remaining_receive: u64,
+}
+
+/// The maker repriced what is left of an offer, possibly after topping up or
+/// withdrawing part of the deposit.
+#[event]
+pub struct OfferUpdated {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub remaining_deposit: u64,
+    pub remaining_receive: u64,
+}
+
+/// Emitted when an offer is refunded by its maker, closed after expiry or
+/// redeemed before it was filled.
+#[event]
+pub struct OfferRefunded {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub refund_amount: u64,
+}
+
+/// A maker-signed order was filled in full, before protocol fees.
+#[event]
+pub struct OrderFilled {
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub nonce: u64,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub deposit_amount: u64,
+    pub receive_amount: u64,
+}
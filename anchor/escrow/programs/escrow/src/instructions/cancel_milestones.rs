@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+use crate::{
+    state::MilestoneEscrow,
+    utils::{harvest_withheld_fees, transfer_checked},
+};
+
+/// Also closes an escrow whose milestones have all been released.
+#[derive(Accounts)]
+pub struct CancelMilestones<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mut, address = milestone_escrow.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        seeds = [MilestoneEscrow::SEED, payer.key.as_ref(), milestone_escrow.seed.to_le_bytes().as_ref()],
+        bump = milestone_escrow.bump
+    )]
+    pub milestone_escrow: Account<'info, MilestoneEscrow>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = milestone_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> CancelMilestones<'info> {
+    /// Returns whatever is left in the vault, which is the unreleased
+    /// milestones, to the payer and closes the vault.
+    pub fn cancel(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let amount = self.vault.amount;
+
+        if amount > 0 {
+            let cpi_accounts = TransferChecked {
+                from: self.vault.to_account_info(),
+                to: self.payer_ata.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority: self.milestone_escrow.to_account_info(),
+            };
+
+            self.milestone_escrow.with_signer_seeds(|signer_seeds| {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                )
+                .with_remaining_accounts(remaining_accounts.to_vec());
+
+                transfer_checked(cpi_ctx, amount, self.mint.decimals)
+            })?;
+        }
+
+        harvest_withheld_fees(
+            &self.token_program,
+            &self.mint,
+            self.vault.to_account_info(),
+        )?;
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.payer.to_account_info(),
+            authority: self.milestone_escrow.to_account_info(),
+        };
+
+        self.milestone_escrow.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+
+            close_account(cpi_ctx)
+        })
+    }
+}
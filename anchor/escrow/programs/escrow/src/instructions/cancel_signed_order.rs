@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+
+use crate::{instructions::SignedOrder, state::FilledOrder};
+
+/// Spends the nonce of a signed order so it can no longer be filled, without
+/// touching the maker's other orders or their delegate approval.
+#[derive(Accounts)]
+#[instruction(order: SignedOrder)]
+pub struct CancelSignedOrder<'info> {
+    #[account(mut, address = order.maker)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        init,
+        payer = maker,
+        space = 8 + FilledOrder::INIT_SPACE,
+        seeds = [FilledOrder::SEED, maker.key.as_ref(), order.nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub filled_order: Account<'info, FilledOrder>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl CancelSignedOrder<'_> {
+    pub fn cancel(&mut self, order: &SignedOrder, bumps: &CancelSignedOrderBumps) -> Result<()> {
+        self.filled_order.set_inner(FilledOrder {
+            maker: order.maker,
+            nonce: order.nonce,
+            payer: self.maker.key(),
+            expires_at: order.expires_at,
+            bump: bumps.filled_order,
+        });
+
+        Ok(())
+    }
+}
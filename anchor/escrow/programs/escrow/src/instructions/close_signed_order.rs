@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::FilledOrder};
+
+/// Permissionless cleanup returning the rent of a spent nonce to whoever paid
+/// for it. An expired order cannot be filled, so the nonce no longer needs to
+/// be kept.
+#[derive(Accounts)]
+pub struct CloseSignedOrder<'info> {
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        seeds = [FilledOrder::SEED, filled_order.maker.as_ref(), filled_order.nonce.to_le_bytes().as_ref()],
+        bump = filled_order.bump
+    )]
+    pub filled_order: Account<'info, FilledOrder>,
+}
+
+impl CloseSignedOrder<'_> {
+    pub fn close(&mut self) -> Result<()> {
+        require!(
+            Clock::get()?.unix_timestamp >= self.filled_order.expires_at,
+            EscrowError::OfferNotExpired
+        );
+
+        Ok(())
+    }
+}
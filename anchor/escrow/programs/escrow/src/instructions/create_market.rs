@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::Market;
+
+/// Anyone may create the market for a pair; offers on it cannot be made until
+/// it exists.
+#[derive(Accounts)]
+pub struct CreateMarket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint_x: InterfaceAccount<'info, Mint>,
+    pub mint_y: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<Market>(),
+        seeds = [Market::SEED, mint_x.key().as_ref(), mint_y.key().as_ref()],
+        bump,
+    )]
+    pub market: AccountLoader<'info, Market>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateMarket<'info> {
+    pub fn create_market(&mut self, bumps: &CreateMarketBumps) -> Result<()> {
+        let mut market = self.market.load_init()?;
+
+        market.mint_x = self.mint_x.key();
+        market.mint_y = self.mint_y.key();
+        market.bump = bumps.market;
+
+        Ok(())
+    }
+}
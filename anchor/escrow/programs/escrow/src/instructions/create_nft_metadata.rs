@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{error::EscrowError, state::NftMetadata};
+
+/// Only the mint authority can record a collection, so this has to happen
+/// before the authority is revoked to fix the supply.
+#[derive(Accounts)]
+pub struct CreateNftMetadata<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        constraint = mint.mint_authority == Some(authority.key()).into()
+            @ EscrowError::Unauthorized
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + NftMetadata::INIT_SPACE,
+        seeds = [NftMetadata::SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub metadata: Account<'info, NftMetadata>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateNftMetadata<'info> {
+    pub fn create_nft_metadata(
+        &mut self,
+        collection: Pubkey,
+        bumps: &CreateNftMetadataBumps,
+    ) -> Result<()> {
+        self.metadata.set_inner(NftMetadata {
+            mint: self.mint.key(),
+            collection,
+            bump: bumps.metadata,
+        });
+
+        Ok(())
+    }
+}
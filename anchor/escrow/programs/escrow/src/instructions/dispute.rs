@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Arbitration};
+
+#[derive(Accounts)]
+pub struct Dispute<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = [arbitration.payer, arbitration.payee].contains(authority.key)
+            @ EscrowError::Unauthorized,
+        seeds = [Arbitration::SEED, arbitration.payer.as_ref(), arbitration.seed.to_le_bytes().as_ref()],
+        bump = arbitration.bump
+    )]
+    pub arbitration: Account<'info, Arbitration>,
+}
+
+impl Dispute<'_> {
+    /// Lets the arbiter settle the payment. Either party may raise a dispute.
+    pub fn dispute(&mut self) -> Result<()> {
+        self.arbitration.disputed = true;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+use solana_sdk_ids::sysvar::instructions as instructions_sysvar;
+
+use crate::{
+    error::EscrowError,
+    state::{Config, FilledOrder},
+    utils::{amount_with_fee, required, transfer_checked, validate_mint, verify_ed25519_signature},
+};
+
+/// An offer the maker signs off-chain instead of funding a vault. The maker
+/// signs `SignedOrder::message` with their wallet key.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SignedOrder {
+    pub maker: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub deposit_amount: u64,
+    pub receive_amount: u64,
+    pub nonce: u64,
+    pub expires_at: i64,
+}
+
+impl SignedOrder {
+    pub const DELEGATE_SEED: &'static [u8] = b"delegate";
+
+    pub const DOMAIN: &'static [u8] = b"escrow:signed-order";
+
+    /// The Borsh-serialized order, prefixed with `DOMAIN` and this program's
+    /// ID so a signature is only valid for this deployment.
+    pub fn message(&self) -> Result<Vec<u8>> {
+        let mut message = [Self::DOMAIN, crate::ID.as_ref()].concat();
+        self.serialize(&mut message)?;
+
+        Ok(message)
+    }
+}
+
+/// Must directly follow the Ed25519 program instruction that verifies the
+/// maker's signature over the order. The maker funds the order by approving
+/// `delegate` for at least `deposit_amount` on their `mint_x` token account.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(order: SignedOrder)]
+pub struct FillSignedOrder<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    #[account(address = order.maker)]
+    pub maker: SystemAccount<'info>,
+
+    #[account(address = order.mint_x)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = order.mint_y)]
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_x
+    )]
+    pub maker_ata_x: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_y,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_y
+    )]
+    pub maker_ata_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_x,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_x
+    )]
+    pub taker_ata_x: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_y
+    )]
+    pub taker_ata_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Holds no data; it only signs as the delegate the maker approved.
+    #[account(seeds = [SignedOrder::DELEGATE_SEED], bump)]
+    pub delegate: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = taker,
+        space = 8 + FilledOrder::INIT_SPACE,
+        seeds = [FilledOrder::SEED, order.maker.as_ref(), order.nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub filled_order: Account<'info, FilledOrder>,
+
+    #[account(seeds = [Config::SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(address = config.treasury)]
+    pub treasury: SystemAccount<'info>,
+
+    /// Only needed when a protocol fee is charged.
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_y,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_y
+    )]
+    pub treasury_ata_y: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// CHECK: Address is checked against the Instructions sysvar.
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> FillSignedOrder<'info> {
+    /// Checks the order and the maker's signature over it, and spends its
+    /// nonce.
+    pub fn fill(&mut self, order: &SignedOrder, bumps: &FillSignedOrderBumps) -> Result<()> {
+        require!(order.deposit_amount > 0, EscrowError::InvalidDepositAmount);
+        require!(order.receive_amount > 0, EscrowError::InvalidReceiveAmount);
+        require!(
+            Clock::get()?.unix_timestamp < order.expires_at,
+            EscrowError::OfferExpired
+        );
+
+        validate_mint(&self.mint_x)?;
+        validate_mint(&self.mint_y)?;
+
+        verify_ed25519_signature(&self.instructions, &order.maker, &order.message()?)?;
+
+        self.filled_order.set_inner(FilledOrder {
+            maker: order.maker,
+            nonce: order.nonce,
+            payer: self.taker.key(),
+            expires_at: order.expires_at,
+            bump: bumps.filled_order,
+        });
+
+        Ok(())
+    }
+
+    /// Pays `amount` of `mint_y` to the maker and `fee` to the treasury,
+    /// grossing both up so they arrive in full after any transfer fee.
+    pub fn deposit(
+        &mut self,
+        amount: u64,
+        fee: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.transfer_y(&self.maker_ata_y, amount, remaining_accounts)?;
+
+        if fee > 0 {
+            self.transfer_y(required(&self.treasury_ata_y)?, fee, remaining_accounts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pulls `amount` of `mint_x` from the maker to the taker, signing as the
+    /// maker's delegate.
+    pub fn withdraw(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+        bumps: &FillSignedOrderBumps,
+    ) -> Result<()> {
+        self.transfer_x(&self.taker_ata_x, amount, remaining_accounts, bumps)
+    }
+
+    fn transfer_y(
+        &self,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let cpi_program = self.token_program_y.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.taker_ata_y.to_account_info(),
+            to: to.to_account_info(),
+            mint: self.mint_y.to_account_info(),
+            authority: self.taker.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+        let amount = amount_with_fee(&self.mint_y, amount)?;
+
+        transfer_checked(cpi_ctx, amount, self.mint_y.decimals)
+    }
+
+    fn transfer_x(
+        &self,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+        bumps: &FillSignedOrderBumps,
+    ) -> Result<()> {
+        let cpi_program = self.token_program_x.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.maker_ata_x.to_account_info(),
+            to: to.to_account_info(),
+            mint: self.mint_x.to_account_info(),
+            authority: self.delegate.to_account_info(),
+        };
+
+        let signer_seeds: &[&[&[u8]]] = &[&[SignedOrder::DELEGATE_SEED, &[bumps.delegate]]];
+
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+        transfer_checked(cpi_ctx, amount, self.mint_x.decimals)
+    }
+}
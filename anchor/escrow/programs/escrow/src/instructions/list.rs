@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::EscrowError,
+    state::{Escrow, Market},
+};
+
+/// Permissionless crank listing an offer that was made while its market was
+/// full, once an entry has been freed up.
+#[derive(Accounts)]
+pub struct List<'info> {
+    #[account(
+        mut,
+        seeds = [Escrow::SEED, escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [Market::SEED, escrow.mint_x.as_ref(), escrow.mint_y.as_ref()],
+        bump = market.load()?.bump
+    )]
+    pub market: AccountLoader<'info, Market>,
+}
+
+impl<'info> List<'info> {
+    pub fn list(&mut self) -> Result<()> {
+        require!(
+            !self.escrow.is_listed && !self.escrow.is_filled(),
+            EscrowError::NotListable
+        );
+
+        let mut market = self.market.load_mut()?;
+        require!(!market.is_full(), EscrowError::MarketFull);
+
+        market.insert(self.escrow.key(), &mut self.escrow);
+
+        Ok(())
+    }
+}
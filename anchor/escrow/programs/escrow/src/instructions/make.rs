@@ -1,11 +1,42 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+    token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    state::{DutchAuction, Escrow, Market, NftMetadata},
+    utils::{is_native, required, transfer_checked, transfer_fee, validate_mint, validate_nft},
 };
 
-use crate::state::Escrow;
+/// Optional terms of an offer, all disabled when left as `None`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct MakeOptions {
+    pub min_fill_amount: Option<u64>,
+    pub expires_at: Option<i64>,
+    pub taker: Option<Pubkey>,
+    pub nft_x: Option<NftTerms>,
+    pub nft_y: Option<NftTerms>,
+    pub auction: Option<DutchAuction>,
+    /// SHA-256 hash of a secret the taker must reveal; requires `expires_at`
+    /// and `taker`.
+    pub hashlock: Option<[u8; 32]>,
+    /// Mints a receipt token for the position to the maker, making it
+    /// transferable.
+    pub receipt: bool,
+}
 
+/// Marks a side of the offer as a single NFT, optionally from `collection`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NftTerms {
+    pub collection: Option<Pubkey>,
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 #[instruction(seed: u64)]
 pub struct Make<'info> {
@@ -15,38 +46,78 @@ pub struct Make<'info> {
     pub mint_x: InterfaceAccount<'info, Mint>,
     pub mint_y: InterfaceAccount<'info, Mint>,
 
+    /// Omitted when `mint_x` is native SOL, which is paid from `maker` directly.
     #[account(
         mut,
         associated_token::mint = mint_x,
-        associated_token::authority = maker
-    )]
-    pub maker_ata_x: InterfaceAccount<'info, TokenAccount>,
-    #[account(
-        mut,
-        associated_token::mint = mint_x,
-        associated_token::authority = maker
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_x
     )]
-    pub maker_ata_y: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_x: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         init,
         payer = maker,
         space = 8 + Escrow::INIT_SPACE,
-        seeds = [b"escrow", maker.key.as_ref(), seed.to_le_bytes().as_ref() ],
+        seeds = [Escrow::SEED, maker.key.as_ref(), seed.to_le_bytes().as_ref()],
         bump,
     )]
     pub escrow: Account<'info, Escrow>,
 
+    #[account(
+        mut,
+        seeds = [Market::SEED, mint_x.key().as_ref(), mint_y.key().as_ref()],
+        bump = market.load()?.bump
+    )]
+    pub market: AccountLoader<'info, Market>,
+
+    /// Omitted when `mint_x` is native SOL, which is held by `escrow` itself.
     #[account(
         init,
         payer = maker,
         associated_token::mint = mint_x,
         associated_token::authority = escrow,
+        associated_token::token_program = token_program_x
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Only for offers made with a receipt.
+    #[account(
+        init,
+        payer = maker,
+        seeds = [Escrow::RECEIPT_SEED, escrow.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = escrow,
+        mint::token_program = token_program_x
+    )]
+    pub receipt_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        init,
+        payer = maker,
+        associated_token::mint = receipt_mint,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_x
+    )]
+    pub maker_receipt_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Holds the `mint_y` proceeds of an offer made with a receipt. Omitted
+    /// when `mint_y` is native SOL, which `escrow` holds itself.
+    #[account(
+        init,
+        payer = maker,
+        associated_token::mint = mint_y,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program_y
+    )]
+    pub proceeds: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Only needed to check the collection of an NFT leg.
+    pub metadata_x: Option<Box<Account<'info, NftMetadata>>>,
+    pub metadata_y: Option<Box<Account<'info, NftMetadata>>>,
 
     pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
@@ -56,34 +127,165 @@ impl<'info> Make<'info> {
         seed: u64,
         deposit_amount: u64,
         receive_amount: u64,
+        options: MakeOptions,
         bumps: &MakeBumps,
     ) -> Result<()> {
+        let MakeOptions {
+            min_fill_amount,
+            expires_at,
+            taker,
+            nft_x,
+            nft_y,
+            auction,
+            hashlock,
+            receipt,
+        } = options;
+
+        require!(deposit_amount > 0, EscrowError::InvalidDepositAmount);
+        require!(receive_amount > 0, EscrowError::InvalidReceiveAmount);
+
+        if let Some(min_fill_amount) = min_fill_amount {
+            require!(
+                min_fill_amount > 0 && min_fill_amount <= receive_amount,
+                EscrowError::InvalidMinFillAmount
+            );
+        }
+
+        if let Some(expires_at) = expires_at {
+            require!(
+                expires_at > Clock::get()?.unix_timestamp,
+                EscrowError::InvalidExpiry
+            );
+        }
+
+        // The timeout is what lets the maker reclaim a hash-timelocked offer.
+        // The first take reveals the secret on-chain, so only the named taker
+        // may use it.
+        if hashlock.is_some() {
+            require!(expires_at.is_some(), EscrowError::InvalidExpiry);
+            require!(taker.is_some(), EscrowError::HashlockWithoutTaker);
+        }
+
+        if let Some(auction) = &auction {
+            auction.validate()?;
+            require!(
+                auction.start_receive_amount == receive_amount,
+                EscrowError::InvalidAuction
+            );
+        }
+
+        validate_mint(&self.mint_x)?;
+        validate_mint(&self.mint_y)?;
+
+        if let Some(NftTerms { collection }) = nft_x {
+            require!(deposit_amount == 1, EscrowError::InvalidNftAmount);
+            validate_nft(&self.mint_x, self.metadata_x.as_deref(), collection)?;
+        }
+
+        if let Some(NftTerms { collection }) = nft_y {
+            require!(receive_amount == 1, EscrowError::InvalidNftAmount);
+            validate_nft(&self.mint_y, self.metadata_y.as_deref(), collection)?;
+        }
+
+        let native_x = is_native(&self.mint_x.key());
+        require!(
+            native_x == (self.maker_ata_x.is_none() && self.vault.is_none()),
+            EscrowError::InvalidTokenAccounts
+        );
+
+        let native_y = is_native(&self.mint_y.key());
+        require!(
+            self.receipt_mint.is_some() == receipt
+                && self.maker_receipt_ata.is_some() == receipt
+                && self.proceeds.is_some() == (receipt && !native_y),
+            EscrowError::InvalidTokenAccounts
+        );
+
+        // The vault only holds what is left after any Token-2022 transfer fee.
+        let deposited = deposit_amount
+            .checked_sub(transfer_fee(&self.mint_x, deposit_amount)?)
+            .ok_or(EscrowError::MathOverflow)?;
+        require!(deposited > 0, EscrowError::InvalidDepositAmount);
+
         self.escrow.set_inner(Escrow {
             seed,
             maker: self.maker.key(),
             mint_x: self.mint_x.key(),
             mint_y: self.mint_y.key(),
-            deposit_amount,
+            deposit_amount: deposited,
             receive_amount,
+            remaining_deposit: deposited,
+            remaining_receive: receive_amount,
+            min_fill_amount,
+            expires_at,
+            taker,
+            auction,
+            hashlock,
+            receipt,
+            is_listed: false,
             bump: bumps.escrow,
         });
 
+        self.market
+            .load_mut()?
+            .insert(self.escrow.key(), &mut self.escrow);
+
         Ok(())
     }
 
-    pub fn deposit(&mut self, amount: u64) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
+    pub fn deposit(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if self.escrow.is_native_x() {
+            let cpi_ctx = CpiContext::new(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.maker.to_account_info(),
+                    to: self.escrow.to_account_info(),
+                },
+            );
+
+            return transfer(cpi_ctx, amount);
+        }
+
+        let cpi_program = self.token_program_x.to_account_info();
 
         let cpi_accounts = TransferChecked {
-            from: self.maker_ata_x.to_account_info(),
-            to: self.vault.to_account_info(),
+            from: required(&self.maker_ata_x)?.to_account_info(),
+            to: required(&self.vault)?.to_account_info(),
             mint: self.mint_x.to_account_info(),
             authority: self.maker.to_account_info(),
         };
 
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
 
         transfer_checked(cpi_ctx, amount, self.mint_x.decimals)?;
         Ok(())
     }
+
+    /// Mints the position's single receipt token to the maker.
+    pub fn issue_receipt(&mut self) -> Result<()> {
+        let (Some(receipt_mint), Some(maker_receipt_ata)) =
+            (&self.receipt_mint, &self.maker_receipt_ata)
+        else {
+            return Ok(());
+        };
+
+        let cpi_program = self.token_program_x.to_account_info();
+
+        let cpi_accounts = MintTo {
+            mint: receipt_mint.to_account_info(),
+            to: maker_receipt_ata.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        self.escrow.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+            mint_to(cpi_ctx, 1)
+        })
+    }
 }
@@ -0,0 +1,109 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    state::Arbitration,
+    utils::{transfer_checked, transfer_fee, validate_mint},
+};
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeArbitrated<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Arbitration::INIT_SPACE,
+        seeds = [Arbitration::SEED, payer.key.as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub arbitration: Account<'info, Arbitration>,
+
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = arbitration,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> MakeArbitrated<'info> {
+    pub fn make(
+        &mut self,
+        seed: u64,
+        amount: u64,
+        payee: Pubkey,
+        arbiter: Pubkey,
+        bumps: &MakeArbitratedBumps,
+    ) -> Result<()> {
+        let payer = self.payer.key();
+
+        require!(amount > 0, EscrowError::InvalidDepositAmount);
+        require!(
+            payee != payer && arbiter != payer && arbiter != payee,
+            EscrowError::InvalidArbiter
+        );
+
+        validate_mint(&self.mint)?;
+
+        let amount = amount
+            .checked_sub(transfer_fee(&self.mint, amount)?)
+            .ok_or(EscrowError::MathOverflow)?;
+        require!(amount > 0, EscrowError::InvalidDepositAmount);
+
+        self.arbitration.set_inner(Arbitration {
+            seed,
+            payer,
+            payee,
+            arbiter,
+            mint: self.mint.key(),
+            amount,
+            disputed: false,
+            bump: bumps.arbitration,
+        });
+
+        Ok(())
+    }
+
+    pub fn deposit(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.payer_ata.to_account_info(),
+            to: self.vault.to_account_info(),
+            mint: self.mint.to_account_info(),
+            authority: self.payer.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
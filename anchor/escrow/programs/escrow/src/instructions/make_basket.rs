@@ -0,0 +1,145 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{create, AssociatedToken, Create},
+    token_interface::{Mint, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    state::{Basket, BasketLeg},
+    utils::{require_ata, transfer_checked, transfer_fee, validate_basket_mint},
+};
+
+/// Each deposit leg passes `[mint, maker_ata, vault]` through
+/// `remaining_accounts`, in the same order as `deposits`. Vaults are created
+/// here as the basket's associated token accounts.
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeBasket<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        init,
+        payer = maker,
+        space = 8 + Basket::INIT_SPACE,
+        seeds = [Basket::SEED, maker.key.as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub basket: Account<'info, Basket>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> MakeBasket<'info> {
+    pub fn make(
+        &mut self,
+        seed: u64,
+        deposits: Vec<BasketLeg>,
+        asks: Vec<BasketLeg>,
+        expires_at: Option<i64>,
+        taker: Option<Pubkey>,
+        bumps: &MakeBasketBumps,
+    ) -> Result<()> {
+        Basket::validate_legs(&asks)?;
+        require!(
+            asks.iter().all(|leg| leg.amount > 0),
+            EscrowError::InvalidReceiveAmount
+        );
+
+        if let Some(expires_at) = expires_at {
+            require!(
+                expires_at > Clock::get()?.unix_timestamp,
+                EscrowError::InvalidExpiry
+            );
+        }
+
+        self.basket.set_inner(Basket {
+            seed,
+            maker: self.maker.key(),
+            deposits,
+            asks,
+            expires_at,
+            taker,
+            bump: bumps.basket,
+        });
+
+        Ok(())
+    }
+
+    /// Moves every deposit leg into its vault and returns the legs net of any
+    /// transfer fee, which is what the vaults end up holding.
+    pub fn deposit(
+        &mut self,
+        deposits: Vec<BasketLeg>,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<Vec<BasketLeg>> {
+        Basket::validate_legs(&deposits)?;
+        require!(
+            remaining_accounts.len() == deposits.len() * 3,
+            EscrowError::InvalidTokenAccounts
+        );
+
+        let token_program = self.token_program.key();
+        let mut deposited = Vec::with_capacity(deposits.len());
+
+        for (leg, accounts) in deposits.iter().zip(remaining_accounts.chunks(3)) {
+            let [mint, maker_ata, vault] = accounts else {
+                return err!(EscrowError::InvalidTokenAccounts);
+            };
+
+            require_keys_eq!(mint.key(), leg.mint, EscrowError::InvalidTokenAccounts);
+            require_keys_eq!(
+                *mint.owner,
+                token_program,
+                EscrowError::InvalidTokenAccounts
+            );
+            require!(leg.amount > 0, EscrowError::InvalidDepositAmount);
+
+            let mint = InterfaceAccount::<Mint>::try_from(mint)?;
+            validate_basket_mint(&mint)?;
+
+            require_ata(maker_ata, self.maker.key, &leg.mint, &token_program)?;
+            require_ata(vault, &self.basket.key(), &leg.mint, &token_program)?;
+
+            let cpi_ctx = CpiContext::new(
+                self.associated_token_program.to_account_info(),
+                Create {
+                    payer: self.maker.to_account_info(),
+                    associated_token: vault.clone(),
+                    authority: self.basket.to_account_info(),
+                    mint: mint.to_account_info(),
+                    system_program: self.system_program.to_account_info(),
+                    token_program: self.token_program.to_account_info(),
+                },
+            );
+            create(cpi_ctx)?;
+
+            let cpi_ctx = CpiContext::new(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: maker_ata.clone(),
+                    to: vault.clone(),
+                    mint: mint.to_account_info(),
+                    authority: self.maker.to_account_info(),
+                },
+            );
+            transfer_checked(cpi_ctx, leg.amount, mint.decimals)?;
+
+            let amount = leg
+                .amount
+                .checked_sub(transfer_fee(&mint, leg.amount)?)
+                .ok_or(EscrowError::MathOverflow)?;
+            require!(amount > 0, EscrowError::InvalidDepositAmount);
+
+            deposited.push(BasketLeg {
+                mint: leg.mint,
+                amount,
+            });
+        }
+
+        Ok(deposited)
+    }
+}
@@ -0,0 +1,123 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    state::{Milestone, MilestoneEscrow, MilestoneTerms, MAX_MILESTONES},
+    utils::{transfer_checked, transfer_fee, validate_mint},
+};
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeMilestones<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + MilestoneEscrow::INIT_SPACE,
+        seeds = [MilestoneEscrow::SEED, payer.key.as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub milestone_escrow: Account<'info, MilestoneEscrow>,
+
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = milestone_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> MakeMilestones<'info> {
+    /// Records the milestones and returns their total. Any transfer fee on the
+    /// deposit comes out of the last milestone, as the vault only holds what
+    /// is left after it.
+    pub fn make(
+        &mut self,
+        seed: u64,
+        payee: Pubkey,
+        milestones: Vec<MilestoneTerms>,
+        bumps: &MakeMilestonesBumps,
+    ) -> Result<u64> {
+        require!(
+            !milestones.is_empty()
+                && milestones.len() <= MAX_MILESTONES
+                && milestones.iter().all(|milestone| milestone.amount > 0),
+            EscrowError::InvalidMilestones
+        );
+
+        validate_mint(&self.mint)?;
+
+        let total = milestones
+            .iter()
+            .try_fold(0u64, |total, milestone| total.checked_add(milestone.amount))
+            .ok_or(EscrowError::MathOverflow)?;
+
+        let mut milestones: Vec<Milestone> = milestones
+            .into_iter()
+            .map(|terms| Milestone {
+                amount: terms.amount,
+                unlock_at: terms.unlock_at,
+                released: false,
+            })
+            .collect();
+
+        let fee = transfer_fee(&self.mint, total)?;
+        if let Some(last) = milestones.last_mut() {
+            last.amount = last
+                .amount
+                .checked_sub(fee)
+                .ok_or(EscrowError::InvalidMilestones)?;
+            require!(last.amount > 0, EscrowError::InvalidMilestones);
+        }
+
+        self.milestone_escrow.set_inner(MilestoneEscrow {
+            seed,
+            payer: self.payer.key(),
+            payee,
+            mint: self.mint.key(),
+            milestones,
+            bump: bumps.milestone_escrow,
+        });
+
+        Ok(total)
+    }
+
+    /// Funds the vault with `total`.
+    pub fn deposit(&mut self, total: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.payer_ata.to_account_info(),
+            to: self.vault.to_account_info(),
+            mint: self.mint.to_account_info(),
+            authority: self.payer.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+        transfer_checked(cpi_ctx, total, self.mint.decimals)
+    }
+}
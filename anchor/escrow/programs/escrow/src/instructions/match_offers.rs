@@ -0,0 +1,339 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    error::EscrowError,
+    state::{Config, Escrow, Market, OfferMatch},
+    utils::{has_transfer_fee, required},
+};
+
+/// Permissionless crank settling `escrow_a`, which sells `mint_x` for
+/// `mint_y`, against `escrow_b` on the other side of the pair. Proceeds go
+/// straight to the makers, so the caller only pays for any token accounts
+/// created. Mints with a transfer fee are not supported. Token accounts for a native SOL leg are omitted, as in `Take`.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MatchOffers<'info> {
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(mut)]
+    pub maker_a: SystemAccount<'info>,
+    #[account(mut)]
+    pub maker_b: SystemAccount<'info>,
+
+    #[account(mut, address = escrow_a.mint_x)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, address = escrow_a.mint_y)]
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [Escrow::SEED, maker_a.key.as_ref(), escrow_a.seed.to_le_bytes().as_ref()],
+        bump = escrow_a.bump
+    )]
+    pub escrow_a: Box<Account<'info, Escrow>>,
+    #[account(
+        mut,
+        seeds = [Escrow::SEED, maker_b.key.as_ref(), escrow_b.seed.to_le_bytes().as_ref()],
+        bump = escrow_b.bump
+    )]
+    pub escrow_b: Box<Account<'info, Escrow>>,
+
+    #[account(
+        mut,
+        seeds = [Market::SEED, escrow_a.mint_x.as_ref(), escrow_a.mint_y.as_ref()],
+        bump = market_a.load()?.bump
+    )]
+    pub market_a: AccountLoader<'info, Market>,
+    #[account(
+        mut,
+        seeds = [Market::SEED, escrow_b.mint_x.as_ref(), escrow_b.mint_y.as_ref()],
+        bump = market_b.load()?.bump
+    )]
+    pub market_b: AccountLoader<'info, Market>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = escrow_a,
+        associated_token::token_program = token_program_x
+    )]
+    pub vault_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = escrow_b,
+        associated_token::token_program = token_program_y
+    )]
+    pub vault_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+        init_if_needed,
+        payer = caller,
+        associated_token::mint = mint_y,
+        associated_token::authority = maker_a,
+        associated_token::token_program = token_program_y
+    )]
+    pub maker_a_ata_y: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init_if_needed,
+        payer = caller,
+        associated_token::mint = mint_x,
+        associated_token::authority = maker_b,
+        associated_token::token_program = token_program_x
+    )]
+    pub maker_b_ata_x: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(seeds = [Config::SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(mut, address = config.treasury)]
+    pub treasury: SystemAccount<'info>,
+
+    /// Only needed when a protocol fee is charged on a token leg.
+    #[account(
+        init_if_needed,
+        payer = caller,
+        associated_token::mint = mint_x,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_x
+    )]
+    pub treasury_ata_x: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init_if_needed,
+        payer = caller,
+        associated_token::mint = mint_y,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_y
+    )]
+    pub treasury_ata_y: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MatchOffers<'info> {
+    /// Checks both offers can trade with each other at `now` and records the
+    /// match on both.
+    pub fn match_offers(&mut self, now: i64) -> Result<OfferMatch> {
+        // An offer cannot trade with itself, nor can a pair sell a mint for
+        // itself.
+        require_keys_neq!(
+            self.escrow_a.key(),
+            self.escrow_b.key(),
+            EscrowError::OffersDoNotCross
+        );
+        require_keys_neq!(
+            self.mint_x.key(),
+            self.mint_y.key(),
+            EscrowError::OffersDoNotCross
+        );
+        // Makers are paid straight out of the other vault, which leaves
+        // nothing to cover a transfer fee on top of their ask.
+        require!(
+            !has_transfer_fee(&self.mint_x)? && !has_transfer_fee(&self.mint_y)?,
+            EscrowError::UnsupportedMintExtension
+        );
+
+        for escrow in [&self.escrow_a, &self.escrow_b] {
+            require!(!escrow.is_expired(now), EscrowError::OfferExpired);
+            // Hash-timelocked offers can only be taken with their secret.
+            escrow.unlock(None)?;
+            // Proceeds of offers with a receipt are held for the receipt holder.
+            require!(!escrow.receipt, EscrowError::ReceiptRequired);
+        }
+
+        require!(
+            self.escrow_a.can_be_taken_by(self.maker_b.key)
+                && self.escrow_b.can_be_taken_by(self.maker_a.key),
+            EscrowError::UnauthorizedTaker
+        );
+
+        self.escrow_a.reprice(now)?;
+        self.escrow_b.reprice(now)?;
+
+        let offer_match = self.escrow_a.match_with(&mut self.escrow_b, &self.config)?;
+
+        self.market_a
+            .load_mut()?
+            .sync(self.escrow_a.key(), &mut self.escrow_a);
+        self.market_b
+            .load_mut()?
+            .sync(self.escrow_b.key(), &mut self.escrow_b);
+
+        Ok(offer_match)
+    }
+
+    /// Pays `amount` of `mint_x` from `escrow_a` to the other maker and `fee`
+    /// to the treasury.
+    pub fn settle_x(
+        &mut self,
+        amount: u64,
+        fee: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.transfer_x(
+            &self.maker_b,
+            &self.maker_b_ata_x,
+            amount,
+            remaining_accounts,
+        )?;
+
+        if fee > 0 {
+            self.transfer_x(
+                &self.treasury,
+                &self.treasury_ata_x,
+                fee,
+                remaining_accounts,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Pays `amount` of `mint_y` from `escrow_b` to the other maker and `fee`
+    /// to the treasury.
+    pub fn settle_y(
+        &mut self,
+        amount: u64,
+        fee: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.transfer_y(
+            &self.maker_a,
+            &self.maker_a_ata_y,
+            amount,
+            remaining_accounts,
+        )?;
+
+        if fee > 0 {
+            self.transfer_y(
+                &self.treasury,
+                &self.treasury_ata_y,
+                fee,
+                remaining_accounts,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn transfer_x(
+        &self,
+        wallet: &impl ToAccountInfo<'info>,
+        ata: &Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        Self::transfer_from(
+            &self.escrow_a,
+            &self.vault_a,
+            &self.mint_x,
+            &self.token_program_x,
+            wallet,
+            ata,
+            amount,
+            remaining_accounts,
+        )
+    }
+
+    fn transfer_y(
+        &self,
+        wallet: &impl ToAccountInfo<'info>,
+        ata: &Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        Self::transfer_from(
+            &self.escrow_b,
+            &self.vault_b,
+            &self.mint_y,
+            &self.token_program_y,
+            wallet,
+            ata,
+            amount,
+            remaining_accounts,
+        )
+    }
+
+    /// Moves `amount` out of `escrow`, as lamports when it holds native SOL.
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_from(
+        escrow: &Account<'info, Escrow>,
+        vault: &Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
+        wallet: &impl ToAccountInfo<'info>,
+        ata: &Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if escrow.is_native_x() {
+            escrow.sub_lamports(amount)?;
+            wallet.to_account_info().add_lamports(amount)?;
+
+            return Ok(());
+        }
+
+        Escrow::transfer_out(
+            escrow,
+            required(vault)?.to_account_info(),
+            required(ata)?.to_account_info(),
+            mint,
+            token_program,
+            amount,
+            remaining_accounts,
+        )
+    }
+
+    /// Closes whichever offers were filled, along with their vaults.
+    pub fn close_filled(&mut self) -> Result<()> {
+        if self.escrow_a.is_filled() {
+            Self::close(
+                &self.escrow_a,
+                &self.vault_a,
+                &self.mint_x,
+                &self.token_program_x,
+                &self.maker_a,
+            )?;
+        }
+
+        if self.escrow_b.is_filled() {
+            Self::close(
+                &self.escrow_b,
+                &self.vault_b,
+                &self.mint_y,
+                &self.token_program_y,
+                &self.maker_b,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn close(
+        escrow: &Account<'info, Escrow>,
+        vault: &Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
+        maker: &SystemAccount<'info>,
+    ) -> Result<()> {
+        if let Some(vault) = vault {
+            Escrow::close_token_account(
+                escrow,
+                vault.to_account_info(),
+                maker.to_account_info(),
+                mint,
+                token_program,
+            )?;
+        }
+
+        escrow.close(maker.to_account_info())
+    }
+}
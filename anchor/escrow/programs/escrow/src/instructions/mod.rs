@@ -1,5 +1,51 @@
+pub mod cancel_milestones;
+pub mod cancel_signed_order;
+pub mod close_signed_order;
+pub mod create_market;
+pub mod create_nft_metadata;
+pub mod dispute;
+pub mod expire;
+pub mod fill_signed_order;
+pub mod initialize_config;
+pub mod list;
 pub mod make;
+pub mod make_arbitrated;
+pub mod make_basket;
+pub mod make_milestones;
+pub mod match_offers;
+pub mod redeem;
+pub mod refund;
+pub mod refund_basket;
+pub mod release_milestone;
+pub mod settle_arbitration;
 pub mod take;
+pub mod take_basket;
+pub mod take_many;
+pub mod update;
+pub mod update_config;
 
+pub use cancel_milestones::*;
+pub use cancel_signed_order::*;
+pub use close_signed_order::*;
+pub use create_market::*;
+pub use create_nft_metadata::*;
+pub use dispute::*;
+pub use expire::*;
+pub use fill_signed_order::*;
+pub use initialize_config::*;
+pub use list::*;
 pub use make::*;
+pub use make_arbitrated::*;
+pub use make_basket::*;
+pub use make_milestones::*;
+pub use match_offers::*;
+pub use redeem::*;
+pub use refund::*;
+pub use refund_basket::*;
+pub use release_milestone::*;
+pub use settle_arbitration::*;
 pub use take::*;
+pub use take_basket::*;
+pub use take_many::*;
+pub use update::*;
+pub use update_config::*;
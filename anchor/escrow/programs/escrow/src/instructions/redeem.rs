@@ -0,0 +1,173 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{burn, Burn, Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    state::{Escrow, Market},
+    utils::required,
+};
+
+/// Settles an offer made with a receipt for whoever holds the receipt,
+/// whether or not it has been filled. Token accounts for a native SOL leg are
+/// omitted, and those lamports go to the holder when the escrow closes.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    #[account(mut)]
+    pub holder: Signer<'info>,
+
+    #[account(mut, address = escrow.mint_x)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, address = escrow.mint_y)]
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [Escrow::RECEIPT_SEED, escrow.key().as_ref()],
+        bump
+    )]
+    pub receipt_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        token::mint = receipt_mint,
+        token::authority = holder,
+        token::token_program = token_program_x
+    )]
+    pub holder_receipt: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = holder,
+        associated_token::mint = mint_x,
+        associated_token::authority = holder,
+        associated_token::token_program = token_program_x
+    )]
+    pub holder_ata_x: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init_if_needed,
+        payer = holder,
+        associated_token::mint = mint_y,
+        associated_token::authority = holder,
+        associated_token::token_program = token_program_y
+    )]
+    pub holder_ata_y: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+        mut,
+        close = holder,
+        seeds = [Escrow::SEED, escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    #[account(
+        mut,
+        seeds = [Market::SEED, escrow.mint_x.as_ref(), escrow.mint_y.as_ref()],
+        bump = market.load()?.bump
+    )]
+    pub market: AccountLoader<'info, Market>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program_x
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program_y
+    )]
+    pub proceeds: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Redeem<'info> {
+    /// Burns the holder's receipt and delists the offer if it is still open.
+    pub fn burn_receipt(&mut self) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            self.token_program_x.to_account_info(),
+            Burn {
+                mint: self.receipt_mint.to_account_info(),
+                from: self.holder_receipt.to_account_info(),
+                authority: self.holder.to_account_info(),
+            },
+        );
+
+        burn(cpi_ctx, 1)?;
+
+        if !self.escrow.is_filled() {
+            self.market.load_mut()?.remove(self.escrow.key());
+        }
+
+        Ok(())
+    }
+
+    /// Pays out the unfilled deposit and the proceeds to the holder and
+    /// closes both token accounts of the escrow.
+    pub fn withdraw(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if !self.escrow.is_native_x() {
+            self.empty(
+                required(&self.vault)?,
+                required(&self.holder_ata_x)?,
+                &self.mint_x,
+                &self.token_program_x,
+                self.escrow.remaining_deposit,
+                remaining_accounts,
+            )?;
+        }
+
+        if !self.escrow.is_native_y() {
+            let proceeds = required(&self.proceeds)?;
+
+            self.empty(
+                proceeds,
+                required(&self.holder_ata_y)?,
+                &self.mint_y,
+                &self.token_program_y,
+                proceeds.amount,
+                remaining_accounts,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn empty(
+        &self,
+        account: &InterfaceAccount<'info, TokenAccount>,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if amount > 0 {
+            Escrow::transfer_out(
+                &self.escrow,
+                account.to_account_info(),
+                to.to_account_info(),
+                mint,
+                token_program,
+                amount,
+                remaining_accounts,
+            )?;
+        }
+
+        Escrow::close_token_account(
+            &self.escrow,
+            account.to_account_info(),
+            self.holder.to_account_info(),
+            mint,
+            token_program,
+        )
+    }
+}
@@ -0,0 +1,93 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    state::{Escrow, Market},
+    utils::required,
+};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Refund<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(mut, address = escrow.mint_x)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+
+    /// Omitted when `mint_x` is native SOL.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_x: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        seeds = [Escrow::SEED, maker.key.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [Market::SEED, escrow.mint_x.as_ref(), escrow.mint_y.as_ref()],
+        bump = market.load()?.bump
+    )]
+    pub market: AccountLoader<'info, Market>,
+
+    /// Omitted when `mint_x` is native SOL.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Refund<'info> {
+    pub fn withdraw(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        // Lamports held for a native SOL offer go back when the escrow closes.
+        if self.escrow.is_native_x() {
+            return Ok(());
+        }
+
+        let vault = required(&self.vault)?;
+
+        Escrow::transfer_out(
+            &self.escrow,
+            vault.to_account_info(),
+            required(&self.maker_ata_x)?.to_account_info(),
+            &self.mint_x,
+            &self.token_program,
+            vault.amount,
+            remaining_accounts,
+        )
+    }
+
+    pub fn close(&mut self) -> Result<()> {
+        let Some(vault) = &self.vault else {
+            return Ok(());
+        };
+
+        Escrow::close_token_account(
+            &self.escrow,
+            vault.to_account_info(),
+            self.maker.to_account_info(),
+            &self.mint_x,
+            &self.token_program,
+        )
+    }
+}
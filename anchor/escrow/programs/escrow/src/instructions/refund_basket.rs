@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    close_account, CloseAccount, Mint, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    error::EscrowError,
+    state::Basket,
+    utils::{harvest_withheld_fees, require_ata, transfer_checked},
+};
+
+/// Each deposit leg passes `[mint, vault, maker_ata]` through
+/// `remaining_accounts`, in the order stored on the basket.
+#[derive(Accounts)]
+pub struct RefundBasket<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        seeds = [Basket::SEED, maker.key.as_ref(), basket.seed.to_le_bytes().as_ref()],
+        bump = basket.bump
+    )]
+    pub basket: Account<'info, Basket>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> RefundBasket<'info> {
+    /// Returns every deposit leg to the maker and closes the emptied vaults.
+    pub fn withdraw(&mut self, remaining_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        require!(
+            remaining_accounts.len() == self.basket.deposits.len() * 3,
+            EscrowError::InvalidTokenAccounts
+        );
+
+        let token_program = self.token_program.key();
+
+        for (leg, accounts) in self
+            .basket
+            .deposits
+            .iter()
+            .zip(remaining_accounts.chunks(3))
+        {
+            let [mint, vault, maker_ata] = accounts else {
+                return err!(EscrowError::InvalidTokenAccounts);
+            };
+
+            require_keys_eq!(mint.key(), leg.mint, EscrowError::InvalidTokenAccounts);
+            require_ata(vault, &self.basket.key(), &leg.mint, &token_program)?;
+
+            let mint = InterfaceAccount::<Mint>::try_from(mint)?;
+
+            let cpi_accounts = TransferChecked {
+                from: vault.clone(),
+                to: maker_ata.clone(),
+                mint: mint.to_account_info(),
+                authority: self.basket.to_account_info(),
+            };
+
+            self.basket.with_signer_seeds(|signer_seeds| {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                );
+
+                transfer_checked(cpi_ctx, leg.amount, mint.decimals)
+            })?;
+
+            harvest_withheld_fees(&self.token_program, &mint, vault.clone())?;
+
+            let cpi_accounts = CloseAccount {
+                account: vault.clone(),
+                destination: self.maker.to_account_info(),
+                authority: self.basket.to_account_info(),
+            };
+
+            self.basket.with_signer_seeds(|signer_seeds| {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                );
+
+                close_account(cpi_ctx)
+            })?;
+        }
+
+        Ok(())
+    }
+}
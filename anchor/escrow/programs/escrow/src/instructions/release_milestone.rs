@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{error::EscrowError, state::MilestoneEscrow, utils::transfer_checked};
+
+#[derive(Accounts)]
+pub struct ReleaseMilestone<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub payee: SystemAccount<'info>,
+
+    #[account(address = milestone_escrow.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = payee,
+        associated_token::token_program = token_program
+    )]
+    pub payee_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        has_one = payer,
+        has_one = payee,
+        seeds = [MilestoneEscrow::SEED, payer.key.as_ref(), milestone_escrow.seed.to_le_bytes().as_ref()],
+        bump = milestone_escrow.bump
+    )]
+    pub milestone_escrow: Account<'info, MilestoneEscrow>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = milestone_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ReleaseMilestone<'info> {
+    /// Pays milestone `index` out of the vault to the payee. Milestones can be
+    /// released in any order once unlocked.
+    pub fn release(&mut self, index: u8, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+
+        let milestone = self
+            .milestone_escrow
+            .milestones
+            .get_mut(index as usize)
+            .ok_or(EscrowError::InvalidMilestones)?;
+
+        require!(!milestone.released, EscrowError::MilestoneReleased);
+        require!(milestone.is_unlocked(now), EscrowError::MilestoneLocked);
+
+        milestone.released = true;
+        let amount = milestone.amount;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to: self.payee_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            authority: self.milestone_escrow.to_account_info(),
+        };
+
+        self.milestone_escrow.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds)
+                .with_remaining_accounts(remaining_accounts.to_vec());
+
+            transfer_checked(cpi_ctx, amount, self.mint.decimals)
+        })
+    }
+}
@@ -0,0 +1,174 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+use crate::{
+    error::EscrowError,
+    state::Arbitration,
+    utils::{harvest_withheld_fees, required, transfer_checked},
+};
+
+/// Shared by `release`, `refund_arbitrated` and `arbitrate`, which differ only
+/// in who may sign and how the vault is split. A party's token account may be
+/// omitted when it receives nothing.
+#[derive(Accounts)]
+pub struct SettleArbitration<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+    pub payee: SystemAccount<'info>,
+
+    #[account(mut, address = arbitration.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = payee,
+        associated_token::token_program = token_program
+    )]
+    pub payee_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        has_one = payee,
+        seeds = [Arbitration::SEED, payer.key.as_ref(), arbitration.seed.to_le_bytes().as_ref()],
+        bump = arbitration.bump
+    )]
+    pub arbitration: Account<'info, Arbitration>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = arbitration,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SettleArbitration<'info> {
+    /// The payer pays the whole vault out to the payee.
+    pub fn release(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require_keys_eq!(
+            self.authority.key(),
+            self.arbitration.payer,
+            EscrowError::Unauthorized
+        );
+
+        self.settle(100, remaining_accounts)
+    }
+
+    /// The payee hands the whole vault back to the payer.
+    pub fn refund(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require_keys_eq!(
+            self.authority.key(),
+            self.arbitration.payee,
+            EscrowError::Unauthorized
+        );
+
+        self.settle(0, remaining_accounts)
+    }
+
+    /// The arbiter splits a disputed vault, giving `payee_percent` to the
+    /// payee and the rest to the payer.
+    pub fn arbitrate(
+        &mut self,
+        payee_percent: u8,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require_keys_eq!(
+            self.authority.key(),
+            self.arbitration.arbiter,
+            EscrowError::Unauthorized
+        );
+        require!(self.arbitration.disputed, EscrowError::NotDisputed);
+        require!(payee_percent <= 100, EscrowError::InvalidSplit);
+
+        self.settle(payee_percent, remaining_accounts)
+    }
+
+    /// Pays out the vault, rounding the payee's share down, and closes it to
+    /// the payer.
+    fn settle(
+        &mut self,
+        payee_percent: u8,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let total = self.vault.amount;
+        let to_payee = u64::try_from(total as u128 * payee_percent as u128 / 100)
+            .map_err(|_| EscrowError::MathOverflow)?;
+        let to_payer = total - to_payee;
+
+        if to_payee > 0 {
+            self.transfer(required(&self.payee_ata)?, to_payee, remaining_accounts)?;
+        }
+
+        if to_payer > 0 {
+            self.transfer(required(&self.payer_ata)?, to_payer, remaining_accounts)?;
+        }
+
+        harvest_withheld_fees(
+            &self.token_program,
+            &self.mint,
+            self.vault.to_account_info(),
+        )?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.payer.to_account_info(),
+            authority: self.arbitration.to_account_info(),
+        };
+
+        self.arbitration.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+            close_account(cpi_ctx)
+        })
+    }
+
+    fn transfer(
+        &self,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to: to.to_account_info(),
+            mint: self.mint.to_account_info(),
+            authority: self.arbitration.to_account_info(),
+        };
+
+        self.arbitration.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds)
+                .with_remaining_accounts(remaining_accounts.to_vec());
+
+            transfer_checked(cpi_ctx, amount, self.mint.decimals)
+        })
+    }
+}
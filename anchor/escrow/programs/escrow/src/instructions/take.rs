@@ -1,140 +1,224 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{
-        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
-        TransferChecked,
-    },
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
 };
 
-use crate::state::Escrow;
+use crate::{
+    error::EscrowError,
+    state::{Config, Escrow, Market},
+    utils::{amount_with_fee, required, transfer_checked},
+};
 
+/// Token accounts for a native SOL leg are omitted, and lamports move between
+/// the wallets (and the escrow account for `mint_x`) instead.
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Take<'info> {
     #[account(mut)]
     pub taker: Signer<'info>,
 
-    #[account(address = escrow.mint_x)]
-    pub mint_x: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+
+    #[account(mut, address = escrow.mint_x)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
     #[account(address = escrow.mint_y)]
-    pub mint_y: InterfaceAccount<'info, Mint>,
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
         associated_token::mint = escrow.mint_x,
-        associated_token::authority = taker
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_x
     )]
-    pub taker_ata_x: InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_x: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
     #[account(
         mut,
         associated_token::mint = escrow.mint_y,
-        associated_token::authority = taker
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_y
     )]
-    pub taker_ata_y: InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_y: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     #[account(
-        mut,
-        associated_token::mint = escrow.mint_x,
-        associated_token::authority = escrow.maker
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_y,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_y
     )]
-    pub maker_ata_x: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_y: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
     #[account(
         mut,
-        associated_token::mint = escrow.mint_y,
-        associated_token::authority = escrow.maker
+        has_one = maker,
+        constraint = escrow.can_be_taken_by(taker.key) @ EscrowError::UnauthorizedTaker,
+        seeds = [Escrow::SEED, maker.key.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
     )]
-    pub maker_ata_y: InterfaceAccount<'info, TokenAccount>,
+    pub escrow: Account<'info, Escrow>,
 
     #[account(
         mut,
-        seeds = [b"escrow", escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()],
-        bump = escrow.bump
+        seeds = [Market::SEED, escrow.mint_x.as_ref(), escrow.mint_y.as_ref()],
+        bump = market.load()?.bump
     )]
-    pub escrow: Account<'info, Escrow>,
+    pub market: AccountLoader<'info, Market>,
 
     #[account(
         mut,
         associated_token::mint = escrow.mint_x,
         associated_token::authority = escrow,
+        associated_token::token_program = token_program_x
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
-    pub token_program: Interface<'info, TokenInterface>,
+    /// Receives the maker's share of `mint_y` instead of `maker_ata_y` when
+    /// the offer was made with a receipt.
+    #[account(
+        mut,
+        associated_token::mint = escrow.mint_y,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program_y
+    )]
+    pub proceeds: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(seeds = [Config::SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(mut, address = config.treasury)]
+    pub treasury: SystemAccount<'info>,
+
+    /// Only needed when a protocol fee is charged and `mint_y` is not native
+    /// SOL.
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_y,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_y
+    )]
+    pub treasury_ata_y: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Take<'info> {
-    pub fn deposit(&mut self) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
-
-        let cpi_accounts = TransferChecked {
-            from: self.taker_ata_y.to_account_info(),
-            to: self.maker_ata_y.to_account_info(),
-            mint: self.maker_ata_y.to_account_info(),
-            authority: self.taker.to_account_info(),
-        };
-
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-
-        transfer_checked(cpi_ctx, self.escrow.receive_amount, self.mint_y.decimals)?;
+    /// Pays `amount` of `mint_y` to the maker, or into the escrow for the
+    /// receipt holder, and `fee` to the treasury, grossing both up so they
+    /// arrive in full after any transfer fee.
+    pub fn deposit(
+        &mut self,
+        amount: u64,
+        fee: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if self.escrow.receipt {
+            self.transfer_y(&self.escrow, &self.proceeds, amount, remaining_accounts)?;
+        } else {
+            self.transfer_y(&self.maker, &self.maker_ata_y, amount, remaining_accounts)?;
+        }
+
+        if fee > 0 {
+            self.transfer_y(
+                &self.treasury,
+                &self.treasury_ata_y,
+                fee,
+                remaining_accounts,
+            )?;
+        }
 
         Ok(())
     }
 
-    pub fn withdraw(&mut self) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
+    /// Releases `amount` of `mint_x` from escrow to the taker.
+    pub fn withdraw(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.transfer_x(&self.taker, &self.taker_ata_x, amount, remaining_accounts)
+    }
 
-        let amount = self.escrow.deposit_amount;
+    fn transfer_y(
+        &self,
+        wallet: &impl ToAccountInfo<'info>,
+        ata: &Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if self.escrow.is_native_y() {
+            let cpi_ctx = CpiContext::new(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.taker.to_account_info(),
+                    to: wallet.to_account_info(),
+                },
+            );
+
+            return transfer(cpi_ctx, amount);
+        }
+
+        let cpi_program = self.token_program_y.to_account_info();
 
         let cpi_accounts = TransferChecked {
-            from: self.vault.to_account_info(),
-            to: self.taker_ata_x.to_account_info(),
-            mint: self.mint_x.to_account_info(),
-            authority: self.escrow.to_account_info(),
+            from: required(&self.taker_ata_y)?.to_account_info(),
+            to: required(ata)?.to_account_info(),
+            mint: self.mint_y.to_account_info(),
+            authority: self.taker.to_account_info(),
         };
 
-        let seed_bytes = self.escrow.seed.to_be_bytes();
-
-        let seeds = &[
-            b"escrow",
-            self.escrow.maker.as_ref(),
-            seed_bytes.as_ref(),
-            &[self.escrow.bump],
-        ];
-
-        let signer_seeds: &[&[&[u8]]] = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
 
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        let amount = amount_with_fee(&self.mint_y, amount)?;
 
-        transfer_checked(cpi_ctx, amount, self.mint_x.decimals)?;
+        transfer_checked(cpi_ctx, amount, self.mint_y.decimals)
+    }
 
-        Ok(())
+    fn transfer_x(
+        &self,
+        wallet: &impl ToAccountInfo<'info>,
+        ata: &Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if self.escrow.is_native_x() {
+            self.escrow.sub_lamports(amount)?;
+            wallet.to_account_info().add_lamports(amount)?;
+
+            return Ok(());
+        }
+
+        Escrow::transfer_out(
+            &self.escrow,
+            required(&self.vault)?.to_account_info(),
+            required(ata)?.to_account_info(),
+            &self.mint_x,
+            &self.token_program_x,
+            amount,
+            remaining_accounts,
+        )
     }
 
     pub fn close(&mut self) -> Result<()> {
-        let cpi_program = self.token_program.to_account_info();
-
-        let cpi_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
-            destination: self.taker.to_account_info(),
-            authority: self.escrow.to_account_info(),
-        };
-
-        let seed_bytes = self.escrow.seed.to_be_bytes();
-
-        let seeds = &[
-            b"escrow",
-            self.escrow.maker.as_ref(),
-            seed_bytes.as_ref(),
-            &[self.escrow.bump],
-        ];
-
-        let signer_seeds: &[&[&[u8]]] = &[&seeds[..]];
-
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-
-        close_account(cpi_ctx)?;
-        Ok(())
+        if let Some(vault) = &self.vault {
+            Escrow::close_token_account(
+                &self.escrow,
+                vault.to_account_info(),
+                self.maker.to_account_info(),
+                &self.mint_x,
+                &self.token_program_x,
+            )?;
+        }
+
+        self.escrow.close(self.maker.to_account_info())
     }
 }
@@ -0,0 +1,189 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    close_account, CloseAccount, Mint, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    error::EscrowError,
+    state::{Basket, Config},
+    utils::{
+        amount_with_fee, harvest_withheld_fees, require_ata, transfer_checked, validate_basket_mint,
+    },
+};
+
+/// `remaining_accounts` holds `[mint, vault, taker_ata]` for each deposit
+/// leg, followed by `[mint, taker_ata, maker_ata, treasury_ata]` for
+/// each ask leg, in the order stored on the basket. The maker's and treasury's
+/// token accounts must already exist.
+#[derive(Accounts)]
+pub struct TakeBasket<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        constraint = basket.can_be_taken_by(taker.key) @ EscrowError::UnauthorizedTaker,
+        seeds = [Basket::SEED, maker.key.as_ref(), basket.seed.to_le_bytes().as_ref()],
+        bump = basket.bump
+    )]
+    pub basket: Account<'info, Basket>,
+
+    #[account(seeds = [Config::SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(address = config.treasury)]
+    pub treasury: SystemAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> TakeBasket<'info> {
+    /// Splits `remaining_accounts` into the deposit legs and the ask legs.
+    pub fn leg_accounts(
+        &self,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<(&'info [AccountInfo<'info>], &'info [AccountInfo<'info>])> {
+        let deposits = self.basket.deposits.len() * 3;
+        let asks = self.basket.asks.len() * 4;
+
+        require!(
+            remaining_accounts.len() == deposits + asks,
+            EscrowError::InvalidTokenAccounts
+        );
+
+        Ok(remaining_accounts.split_at(deposits))
+    }
+
+    /// Pays every ask leg to the maker in full and the protocol fee on top of
+    /// it to the treasury, grossing both up for any transfer fee.
+    pub fn deposit(&mut self, ask_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        let token_program = self.token_program.key();
+
+        for (leg, accounts) in self.basket.asks.iter().zip(ask_accounts.chunks(4)) {
+            let [mint, taker_ata, maker_ata, treasury_ata] = accounts else {
+                return err!(EscrowError::InvalidTokenAccounts);
+            };
+
+            let mint = self.load_mint(mint, &leg.mint)?;
+            require_ata(maker_ata, self.maker.key, &leg.mint, &token_program)?;
+            require_ata(treasury_ata, self.treasury.key, &leg.mint, &token_program)?;
+
+            let fee = self.config.fee(leg.amount)?;
+
+            self.transfer_from_taker(&mint, taker_ata, maker_ata, leg.amount)?;
+
+            if fee > 0 {
+                self.transfer_from_taker(&mint, taker_ata, treasury_ata, fee)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Releases every deposit leg to the taker in full and closes the emptied
+    /// vaults to the maker.
+    pub fn withdraw(&mut self, deposit_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        let token_program = self.token_program.key();
+
+        for (leg, accounts) in self.basket.deposits.iter().zip(deposit_accounts.chunks(3)) {
+            let [mint, vault, taker_ata] = accounts else {
+                return err!(EscrowError::InvalidTokenAccounts);
+            };
+
+            let mint = self.load_mint(mint, &leg.mint)?;
+            require_ata(vault, &self.basket.key(), &leg.mint, &token_program)?;
+
+            self.transfer_from_vault(&mint, vault, taker_ata, leg.amount)?;
+
+            harvest_withheld_fees(&self.token_program, &mint, vault.clone())?;
+
+            let cpi_accounts = CloseAccount {
+                account: vault.clone(),
+                destination: self.maker.to_account_info(),
+                authority: self.basket.to_account_info(),
+            };
+
+            self.basket.with_signer_seeds(|signer_seeds| {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                );
+
+                close_account(cpi_ctx)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn load_mint(
+        &self,
+        mint: &'info AccountInfo<'info>,
+        expected: &Pubkey,
+    ) -> Result<InterfaceAccount<'info, Mint>> {
+        require_keys_eq!(mint.key(), *expected, EscrowError::InvalidTokenAccounts);
+        require_keys_eq!(
+            *mint.owner,
+            self.token_program.key(),
+            EscrowError::InvalidTokenAccounts
+        );
+
+        let mint = InterfaceAccount::<Mint>::try_from(mint)?;
+        validate_basket_mint(&mint)?;
+
+        Ok(mint)
+    }
+
+    fn transfer_from_taker(
+        &self,
+        mint: &InterfaceAccount<'info, Mint>,
+        from: &AccountInfo<'info>,
+        to: &AccountInfo<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: from.clone(),
+                to: to.clone(),
+                mint: mint.to_account_info(),
+                authority: self.taker.to_account_info(),
+            },
+        );
+
+        let amount = amount_with_fee(mint, amount)?;
+
+        transfer_checked(cpi_ctx, amount, mint.decimals)
+    }
+
+    fn transfer_from_vault(
+        &self,
+        mint: &InterfaceAccount<'info, Mint>,
+        vault: &AccountInfo<'info>,
+        to: &AccountInfo<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: vault.clone(),
+            to: to.clone(),
+            mint: mint.to_account_info(),
+            authority: self.basket.to_account_info(),
+        };
+
+        self.basket.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+
+            transfer_checked(cpi_ctx, amount, mint.decimals)
+        })
+    }
+}
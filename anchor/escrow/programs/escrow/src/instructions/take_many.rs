@@ -0,0 +1,281 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    events::{FeesCollected, OfferTaken},
+    state::{Config, Escrow, Market},
+    utils::{
+        amount_with_fee, is_native, require_ata, required, transfer_checked, validate_basket_mint,
+    },
+};
+
+/// `remaining_accounts` holds `[escrow, vault, maker, maker_ata_y]` for each
+/// offer to sweep, best first. The maker's wallet receives the rent of filled
+/// offers. Every offer must sell `mint_x` for `mint_y`, neither of which may
+/// be native SOL or use a transfer hook, and the makers' token accounts must
+/// already exist.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TakeMany<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    #[account(mut)]
+    pub mint_x: Box<InterfaceAccount<'info, Mint>>,
+    pub mint_y: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_x,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_x
+    )]
+    pub taker_ata_x: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_y,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_y
+    )]
+    pub taker_ata_y: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [Market::SEED, mint_x.key().as_ref(), mint_y.key().as_ref()],
+        bump = market.load()?.bump
+    )]
+    pub market: AccountLoader<'info, Market>,
+
+    #[account(seeds = [Config::SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(address = config.treasury)]
+    pub treasury: SystemAccount<'info>,
+
+    /// Only needed when a protocol fee is charged.
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_y,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_y
+    )]
+    pub treasury_ata_y: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    pub token_program_x: Interface<'info, TokenInterface>,
+    pub token_program_y: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TakeMany<'info> {
+    /// Fills the offers in order until the makers have received `max_pay` of
+    /// `mint_y` in total, with protocol fees paid on top, and fails unless
+    /// all of it can be filled. Offers that cannot be taken, such as expired,
+    /// already closed or private ones, are skipped. Returns the events for
+    /// every fill.
+    pub fn take_many(
+        &mut self,
+        max_pay: u64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<Vec<(FeesCollected, OfferTaken)>> {
+        require!(max_pay > 0, EscrowError::InvalidFillAmount);
+        require!(
+            !remaining_accounts.is_empty() && remaining_accounts.len() % 4 == 0,
+            EscrowError::InvalidTokenAccounts
+        );
+        require!(
+            !is_native(&self.mint_x.key()) && !is_native(&self.mint_y.key()),
+            EscrowError::InvalidTokenAccounts
+        );
+
+        validate_basket_mint(&self.mint_x)?;
+        validate_basket_mint(&self.mint_y)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut budget = max_pay;
+        let mut fills = Vec::new();
+
+        for accounts in remaining_accounts.chunks(4) {
+            if budget == 0 {
+                break;
+            }
+
+            let [escrow, vault, maker, maker_ata] = accounts else {
+                return err!(EscrowError::InvalidTokenAccounts);
+            };
+
+            // Offers filled or refunded since the sweep was built are gone.
+            if escrow.owner != &crate::ID || escrow.data_is_empty() {
+                continue;
+            }
+
+            let mut escrow = Account::<Escrow>::try_from(escrow)?;
+            self.check(&escrow, vault, maker, maker_ata)?;
+
+            if !self.can_take(&escrow, now) {
+                continue;
+            }
+
+            escrow.reprice(now)?;
+            let fill_amount = budget.min(escrow.remaining_receive);
+            let payout = match escrow.fill(fill_amount) {
+                Ok(payout) => payout,
+                // Leaves the offer untouched when the fill is below its
+                // minimum or too small to pay anything out.
+                Err(err)
+                    if err == EscrowError::FillBelowMinimum.into()
+                        || err == EscrowError::FillTooSmall.into() =>
+                {
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+
+            // The taker pays the fee on top, so the maker receives
+            // `fill_amount`.
+            let fee_y = self.config.fee(fill_amount)?;
+
+            self.transfer_y(maker_ata, fill_amount)?;
+            if fee_y > 0 {
+                self.transfer_y(&required(&self.treasury_ata_y)?.to_account_info(), fee_y)?;
+            }
+
+            self.transfer_x(&escrow, vault, &self.taker_ata_x.to_account_info(), payout)?;
+
+            self.market.load_mut()?.sync(escrow.key(), &mut escrow);
+
+            fills.push((
+                FeesCollected {
+                    escrow: escrow.key(),
+                    treasury: self.treasury.key(),
+                    mint_x: escrow.mint_x,
+                    fee_x: 0,
+                    mint_y: escrow.mint_y,
+                    fee_y,
+                },
+                OfferTaken {
+                    escrow: escrow.key(),
+                    maker: escrow.maker,
+                    taker: self.taker.key(),
+                    mint_x: escrow.mint_x,
+                    mint_y: escrow.mint_y,
+                    fill_amount,
+                    payout,
+                    remaining_deposit: escrow.remaining_deposit,
+                    remaining_receive: escrow.remaining_receive,
+                },
+            ));
+
+            if escrow.is_filled() {
+                self.close(&escrow, vault, maker)?;
+            } else {
+                escrow.exit(&crate::ID)?;
+            }
+
+            budget -= fill_amount;
+        }
+
+        require!(budget == 0, EscrowError::FillNotAchievable);
+
+        Ok(fills)
+    }
+
+    /// Checks that the accounts passed for an offer in `remaining_accounts`
+    /// belong to it and to this market.
+    fn check(
+        &self,
+        escrow: &Account<'info, Escrow>,
+        vault: &AccountInfo<'info>,
+        maker: &AccountInfo<'info>,
+        maker_ata: &AccountInfo<'info>,
+    ) -> Result<()> {
+        require!(
+            escrow.mint_x == self.mint_x.key() && escrow.mint_y == self.mint_y.key(),
+            EscrowError::InvalidTokenAccounts
+        );
+        require_keys_eq!(escrow.maker, maker.key(), EscrowError::InvalidTokenAccounts);
+        require_ata(
+            vault,
+            &escrow.key(),
+            &escrow.mint_x,
+            self.token_program_x.key,
+        )?;
+        require_ata(
+            maker_ata,
+            &escrow.maker,
+            &escrow.mint_y,
+            self.token_program_y.key,
+        )?;
+
+        Ok(())
+    }
+
+    /// Whether `Take` would let the taker fill this offer at `now` without a
+    /// preimage. Proceeds of offers with a receipt are held for the receipt
+    /// holder, so those are left alone too.
+    fn can_take(&self, escrow: &Escrow, now: i64) -> bool {
+        !escrow.is_expired(now)
+            && escrow.can_be_taken_by(self.taker.key)
+            && escrow.unlock(None).is_ok()
+            && !escrow.receipt
+    }
+
+    /// Pays `amount` of `mint_y` from the taker, grossed up so it arrives in
+    /// full after any transfer fee.
+    fn transfer_y(&self, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            self.token_program_y.to_account_info(),
+            TransferChecked {
+                from: self.taker_ata_y.to_account_info(),
+                to: to.clone(),
+                mint: self.mint_y.to_account_info(),
+                authority: self.taker.to_account_info(),
+            },
+        );
+
+        let amount = amount_with_fee(&self.mint_y, amount)?;
+
+        transfer_checked(cpi_ctx, amount, self.mint_y.decimals)
+    }
+
+    fn transfer_x(
+        &self,
+        escrow: &Account<'info, Escrow>,
+        vault: &AccountInfo<'info>,
+        to: &AccountInfo<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        Escrow::transfer_out(
+            escrow,
+            vault.clone(),
+            to.clone(),
+            &self.mint_x,
+            &self.token_program_x,
+            amount,
+            &[],
+        )
+    }
+
+    fn close(
+        &self,
+        escrow: &Account<'info, Escrow>,
+        vault: &AccountInfo<'info>,
+        maker: &AccountInfo<'info>,
+    ) -> Result<()> {
+        Escrow::close_token_account(
+            escrow,
+            vault.clone(),
+            maker.clone(),
+            &self.mint_x,
+            &self.token_program_x,
+        )?;
+
+        escrow.close(maker.clone())
+    }
+}
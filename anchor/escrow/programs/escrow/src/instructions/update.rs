@@ -0,0 +1,189 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{
+    error::EscrowError,
+    state::{Escrow, Market},
+    utils::{required, transfer_checked, transfer_fee},
+};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum DepositChange {
+    TopUp { amount: u64 },
+    Withdraw { amount: u64 },
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Update<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(address = escrow.mint_x)]
+    pub mint_x: InterfaceAccount<'info, Mint>,
+
+    /// Omitted when `mint_x` is native SOL.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_x: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        has_one = maker,
+        seeds = [Escrow::SEED, maker.key.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        seeds = [Market::SEED, escrow.mint_x.as_ref(), escrow.mint_y.as_ref()],
+        bump = market.load()?.bump
+    )]
+    pub market: AccountLoader<'info, Market>,
+
+    /// Omitted when `mint_x` is native SOL.
+    #[account(
+        mut,
+        associated_token::mint = mint_x,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Update<'info> {
+    /// Reprices what is left of the offer to `receive_amount`, after applying
+    /// an optional change to the amount held in the vault.
+    pub fn update(
+        &mut self,
+        receive_amount: u64,
+        deposit_change: Option<DepositChange>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(receive_amount > 0, EscrowError::InvalidReceiveAmount);
+
+        if let Some(min_fill_amount) = self.escrow.min_fill_amount {
+            require!(
+                min_fill_amount <= receive_amount,
+                EscrowError::InvalidMinFillAmount
+            );
+        }
+        require!(
+            self.escrow.auction.is_none(),
+            EscrowError::AuctionNotUpdatable
+        );
+        // Updating before expiry would let the maker pull a locked deposit.
+        require!(self.escrow.hashlock.is_none(), EscrowError::HashlockActive);
+        // The maker may no longer hold the receipt.
+        require!(!self.escrow.receipt, EscrowError::ReceiptRequired);
+        require!(
+            !self.escrow.is_expired(Clock::get()?.unix_timestamp),
+            EscrowError::OfferExpired
+        );
+
+        let remaining_deposit = match deposit_change {
+            Some(DepositChange::TopUp { amount }) => {
+                require!(amount > 0, EscrowError::InvalidDepositAmount);
+                self.top_up(amount, remaining_accounts)?;
+
+                let deposited = amount
+                    .checked_sub(transfer_fee(&self.mint_x, amount)?)
+                    .ok_or(EscrowError::MathOverflow)?;
+
+                self.escrow
+                    .remaining_deposit
+                    .checked_add(deposited)
+                    .ok_or(EscrowError::MathOverflow)?
+            }
+            Some(DepositChange::Withdraw { amount }) => {
+                // Withdrawing everything is what `refund` is for.
+                require!(
+                    amount > 0 && amount < self.escrow.remaining_deposit,
+                    EscrowError::InvalidDepositAmount
+                );
+                self.withdraw(amount, remaining_accounts)?;
+
+                self.escrow.remaining_deposit - amount
+            }
+            None => self.escrow.remaining_deposit,
+        };
+
+        // Keep the totals consistent with what has already been filled.
+        let escrow = &mut self.escrow;
+        escrow.deposit_amount =
+            escrow.deposit_amount - escrow.remaining_deposit + remaining_deposit;
+        escrow.receive_amount = escrow.receive_amount - escrow.remaining_receive + receive_amount;
+        escrow.remaining_deposit = remaining_deposit;
+        escrow.remaining_receive = receive_amount;
+
+        self.market
+            .load_mut()?
+            .sync(self.escrow.key(), &mut self.escrow);
+
+        Ok(())
+    }
+
+    fn top_up(&mut self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if self.escrow.is_native_x() {
+            let cpi_ctx = CpiContext::new(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.maker.to_account_info(),
+                    to: self.escrow.to_account_info(),
+                },
+            );
+
+            return transfer(cpi_ctx, amount);
+        }
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: required(&self.maker_ata_x)?.to_account_info(),
+            to: required(&self.vault)?.to_account_info(),
+            mint: self.mint_x.to_account_info(),
+            authority: self.maker.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+        transfer_checked(cpi_ctx, amount, self.mint_x.decimals)
+    }
+
+    fn withdraw(&mut self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if self.escrow.is_native_x() {
+            self.escrow.sub_lamports(amount)?;
+            self.maker.add_lamports(amount)?;
+
+            return Ok(());
+        }
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: required(&self.vault)?.to_account_info(),
+            to: required(&self.maker_ata_x)?.to_account_info(),
+            mint: self.mint_x.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        self.escrow.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds)
+                .with_remaining_accounts(remaining_accounts.to_vec());
+
+            transfer_checked(cpi_ctx, amount, self.mint_x.decimals)
+        })
+    }
+}
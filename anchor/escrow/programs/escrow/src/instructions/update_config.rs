@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ EscrowError::Unauthorized,
+        seeds = [Config::SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> UpdateConfig<'info> {
+    pub fn update_config(
+        &mut self,
+        new_admin: Option<Pubkey>,
+        fee_bps: Option<u16>,
+        treasury: Option<Pubkey>,
+    ) -> Result<()> {
+        if let Some(new_admin) = new_admin {
+            self.config.admin = new_admin;
+        }
+
+        if let Some(fee_bps) = fee_bps {
+            require!(fee_bps <= Config::MAX_FEE_BPS, EscrowError::InvalidFee);
+            self.config.fee_bps = fee_bps;
+        }
+
+        if let Some(treasury) = treasury {
+            self.config.treasury = treasury;
+        }
+
+        Ok(())
+    }
+}
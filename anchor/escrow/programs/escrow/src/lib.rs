@@ -1,9 +1,15 @@
 use anchor_lang::prelude::*;
 
+mod error;
+mod events;
 mod instructions;
 mod state;
+mod utils;
 
+use error::EscrowError;
+use events::{FeesCollected, OfferMade, OfferRefunded, OfferTaken, OfferUpdated, OrderFilled};
 use instructions::*;
+use state::{BasketLeg, MilestoneTerms};
 
 declare_id!("2DRj3Gj1e1uhdaZH1tNqASwqjdFEYuX6jxnVucMuQVjB");
 
@@ -11,23 +17,413 @@ declare_id!("2DRj3Gj1e1uhdaZH1tNqASwqjdFEYuX6jxnVucMuQVjB");
 pub mod escrow {
     use super::*;
 
-    pub fn make(
-        ctx: Context<Make>,
+    pub fn make<'info>(
+        ctx: Context<'_, '_, '_, 'info, Make<'info>>,
         seed: u64,
         deposit_amount: u64,
         receive_amount: u64,
+        options: MakeOptions,
     ) -> Result<()> {
         ctx.accounts
-            .make(seed, deposit_amount, receive_amount, &ctx.bumps)?;
+            .make(seed, deposit_amount, receive_amount, options, &ctx.bumps)?;
+        ctx.accounts
+            .deposit(deposit_amount, ctx.remaining_accounts)?;
+        ctx.accounts.issue_receipt()?;
+
+        let escrow = &ctx.accounts.escrow;
+        emit_cpi!(OfferMade {
+            escrow: escrow.key(),
+            maker: escrow.maker,
+            taker: escrow.taker,
+            mint_x: escrow.mint_x,
+            mint_y: escrow.mint_y,
+            deposit_amount: escrow.deposit_amount,
+            receive_amount: escrow.receive_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        fee_bps: u16,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts
+            .initialize_config(fee_bps, treasury, &ctx.bumps)
+    }
+
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        new_admin: Option<Pubkey>,
+        fee_bps: Option<u16>,
+        treasury: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.update_config(new_admin, fee_bps, treasury)
+    }
+
+    pub fn expire<'info>(ctx: Context<'_, '_, '_, 'info, Expire<'info>>) -> Result<()> {
+        require!(!ctx.accounts.escrow.receipt, EscrowError::ReceiptRequired);
+        require!(
+            ctx.accounts.escrow.is_expired(Clock::get()?.unix_timestamp),
+            EscrowError::OfferNotExpired
+        );
+
+        ctx.accounts
+            .market
+            .load_mut()?
+            .remove(ctx.accounts.escrow.key());
+
+        ctx.accounts.withdraw(ctx.remaining_accounts)?;
+        ctx.accounts.close()?;
+
+        let escrow = &ctx.accounts.escrow;
+        emit_cpi!(OfferRefunded {
+            escrow: escrow.key(),
+            maker: escrow.maker,
+            mint_x: escrow.mint_x,
+            mint_y: escrow.mint_y,
+            refund_amount: escrow.remaining_deposit,
+        });
 
         Ok(())
     }
 
-    pub fn take(ctx: Context<Take>) -> Result<()> {
-        ctx.accounts.deposit()?;
-        ctx.accounts.withdraw()?;
+    pub fn refund<'info>(ctx: Context<'_, '_, '_, 'info, Refund<'info>>) -> Result<()> {
+        require!(!ctx.accounts.escrow.receipt, EscrowError::ReceiptRequired);
+        require!(
+            ctx.accounts
+                .escrow
+                .can_be_withdrawn(Clock::get()?.unix_timestamp),
+            EscrowError::HashlockActive
+        );
+
+        ctx.accounts
+            .market
+            .load_mut()?
+            .remove(ctx.accounts.escrow.key());
+
+        ctx.accounts.withdraw(ctx.remaining_accounts)?;
         ctx.accounts.close()?;
 
+        let escrow = &ctx.accounts.escrow;
+        emit_cpi!(OfferRefunded {
+            escrow: escrow.key(),
+            maker: escrow.maker,
+            mint_x: escrow.mint_x,
+            mint_y: escrow.mint_y,
+            refund_amount: escrow.remaining_deposit,
+        });
+
+        Ok(())
+    }
+
+    pub fn take<'info>(
+        ctx: Context<'_, '_, '_, 'info, Take<'info>>,
+        fill_amount: u64,
+        preimage: Option<[u8; 32]>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            !ctx.accounts.escrow.is_expired(now),
+            EscrowError::OfferExpired
+        );
+        ctx.accounts.escrow.unlock(preimage)?;
+
+        ctx.accounts.escrow.reprice(now)?;
+        let payout = ctx.accounts.escrow.fill(fill_amount)?;
+
+        // The taker pays the fee on top, so the maker receives `fill_amount`.
+        let fee_y = ctx.accounts.config.fee(fill_amount)?;
+
+        ctx.accounts
+            .deposit(fill_amount, fee_y, ctx.remaining_accounts)?;
+        ctx.accounts.withdraw(payout, ctx.remaining_accounts)?;
+
+        ctx.accounts
+            .market
+            .load_mut()?
+            .sync(ctx.accounts.escrow.key(), &mut ctx.accounts.escrow);
+
+        emit_cpi!(FeesCollected {
+            escrow: ctx.accounts.escrow.key(),
+            treasury: ctx.accounts.treasury.key(),
+            mint_x: ctx.accounts.mint_x.key(),
+            fee_x: 0,
+            mint_y: ctx.accounts.mint_y.key(),
+            fee_y,
+        });
+
+        let escrow = &ctx.accounts.escrow;
+        emit_cpi!(OfferTaken {
+            escrow: escrow.key(),
+            maker: escrow.maker,
+            taker: ctx.accounts.taker.key(),
+            mint_x: escrow.mint_x,
+            mint_y: escrow.mint_y,
+            fill_amount,
+            payout,
+            remaining_deposit: escrow.remaining_deposit,
+            remaining_receive: escrow.remaining_receive,
+        });
+
+        // A filled offer with a receipt stays open until the receipt is
+        // redeemed for the proceeds.
+        if ctx.accounts.escrow.is_filled() && !ctx.accounts.escrow.receipt {
+            ctx.accounts.close()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn take_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TakeMany<'info>>,
+        max_pay: u64,
+    ) -> Result<()> {
+        let fills = ctx.accounts.take_many(max_pay, ctx.remaining_accounts)?;
+
+        for (fees, fill) in fills {
+            emit_cpi!(fees);
+            emit_cpi!(fill);
+        }
+
+        Ok(())
+    }
+
+    pub fn update<'info>(
+        ctx: Context<'_, '_, '_, 'info, Update<'info>>,
+        receive_amount: u64,
+        deposit_change: Option<DepositChange>,
+    ) -> Result<()> {
+        ctx.accounts
+            .update(receive_amount, deposit_change, ctx.remaining_accounts)?;
+
+        let escrow = &ctx.accounts.escrow;
+        emit_cpi!(OfferUpdated {
+            escrow: escrow.key(),
+            maker: escrow.maker,
+            mint_x: escrow.mint_x,
+            mint_y: escrow.mint_y,
+            remaining_deposit: escrow.remaining_deposit,
+            remaining_receive: escrow.remaining_receive,
+        });
+
+        Ok(())
+    }
+
+    pub fn match_offers<'info>(ctx: Context<'_, '_, '_, 'info, MatchOffers<'info>>) -> Result<()> {
+        let offer_match = ctx.accounts.match_offers(Clock::get()?.unix_timestamp)?;
+
+        // Each maker receives exactly their ask, and the treasury the spread,
+        // which covers at least the protocol fee on both fills.
+        let fee_x = offer_match.payout - offer_match.other_fill;
+        let fee_y = offer_match.other_payout - offer_match.fill;
+
+        ctx.accounts
+            .settle_x(offer_match.other_fill, fee_x, ctx.remaining_accounts)?;
+        ctx.accounts
+            .settle_y(offer_match.fill, fee_y, ctx.remaining_accounts)?;
+
+        let (escrow_a, escrow_b) = (&ctx.accounts.escrow_a, &ctx.accounts.escrow_b);
+
+        emit_cpi!(FeesCollected {
+            escrow: escrow_a.key(),
+            treasury: ctx.accounts.treasury.key(),
+            mint_x: escrow_a.mint_x,
+            fee_x,
+            mint_y: escrow_a.mint_y,
+            fee_y,
+        });
+
+        emit_cpi!(OfferTaken {
+            escrow: escrow_a.key(),
+            maker: escrow_a.maker,
+            taker: escrow_b.maker,
+            mint_x: escrow_a.mint_x,
+            mint_y: escrow_a.mint_y,
+            fill_amount: offer_match.fill,
+            payout: offer_match.payout,
+            remaining_deposit: escrow_a.remaining_deposit,
+            remaining_receive: escrow_a.remaining_receive,
+        });
+        emit_cpi!(OfferTaken {
+            escrow: escrow_b.key(),
+            maker: escrow_b.maker,
+            taker: escrow_a.maker,
+            mint_x: escrow_b.mint_x,
+            mint_y: escrow_b.mint_y,
+            fill_amount: offer_match.other_fill,
+            payout: offer_match.other_payout,
+            remaining_deposit: escrow_b.remaining_deposit,
+            remaining_receive: escrow_b.remaining_receive,
+        });
+
+        ctx.accounts.close_filled()
+    }
+
+    pub fn fill_signed_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, FillSignedOrder<'info>>,
+        order: SignedOrder,
+    ) -> Result<()> {
+        ctx.accounts.fill(&order, &ctx.bumps)?;
+
+        // The taker pays the fee on top, so the maker receives the full
+        // `receive_amount`.
+        let fee_y = ctx.accounts.config.fee(order.receive_amount)?;
+
+        ctx.accounts
+            .deposit(order.receive_amount, fee_y, ctx.remaining_accounts)?;
+        ctx.accounts
+            .withdraw(order.deposit_amount, ctx.remaining_accounts, &ctx.bumps)?;
+
+        emit_cpi!(OrderFilled {
+            maker: order.maker,
+            taker: ctx.accounts.taker.key(),
+            nonce: order.nonce,
+            mint_x: order.mint_x,
+            mint_y: order.mint_y,
+            deposit_amount: order.deposit_amount,
+            receive_amount: order.receive_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_signed_order(ctx: Context<CancelSignedOrder>, order: SignedOrder) -> Result<()> {
+        ctx.accounts.cancel(&order, &ctx.bumps)
+    }
+
+    pub fn close_signed_order(ctx: Context<CloseSignedOrder>) -> Result<()> {
+        ctx.accounts.close()
+    }
+
+    pub fn redeem<'info>(ctx: Context<'_, '_, '_, 'info, Redeem<'info>>) -> Result<()> {
+        let filled = ctx.accounts.escrow.is_filled();
+        require!(
+            filled
+                || ctx
+                    .accounts
+                    .escrow
+                    .can_be_withdrawn(Clock::get()?.unix_timestamp),
+            EscrowError::HashlockActive
+        );
+
+        ctx.accounts.burn_receipt()?;
+        ctx.accounts.withdraw(ctx.remaining_accounts)?;
+
+        if !filled {
+            let escrow = &ctx.accounts.escrow;
+            emit_cpi!(OfferRefunded {
+                escrow: escrow.key(),
+                maker: escrow.maker,
+                mint_x: escrow.mint_x,
+                mint_y: escrow.mint_y,
+                refund_amount: escrow.remaining_deposit,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn make_basket<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MakeBasket<'info>>,
+        seed: u64,
+        deposits: Vec<BasketLeg>,
+        asks: Vec<BasketLeg>,
+        expires_at: Option<i64>,
+        taker: Option<Pubkey>,
+    ) -> Result<()> {
+        let deposits = ctx.accounts.deposit(deposits, ctx.remaining_accounts)?;
+        ctx.accounts
+            .make(seed, deposits, asks, expires_at, taker, &ctx.bumps)
+    }
+
+    pub fn take_basket<'info>(ctx: Context<'_, '_, 'info, 'info, TakeBasket<'info>>) -> Result<()> {
+        require!(
+            !ctx.accounts.basket.is_expired(Clock::get()?.unix_timestamp),
+            EscrowError::OfferExpired
+        );
+
+        let (deposit_accounts, ask_accounts) = ctx.accounts.leg_accounts(ctx.remaining_accounts)?;
+
+        ctx.accounts.deposit(ask_accounts)?;
+        ctx.accounts.withdraw(deposit_accounts)?;
+
         Ok(())
     }
+
+    pub fn refund_basket<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundBasket<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.withdraw(ctx.remaining_accounts)
+    }
+
+    pub fn make_arbitrated<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeArbitrated<'info>>,
+        seed: u64,
+        amount: u64,
+        payee: Pubkey,
+        arbiter: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts
+            .make(seed, amount, payee, arbiter, &ctx.bumps)?;
+        ctx.accounts.deposit(amount, ctx.remaining_accounts)
+    }
+
+    pub fn release<'info>(ctx: Context<'_, '_, '_, 'info, SettleArbitration<'info>>) -> Result<()> {
+        ctx.accounts.release(ctx.remaining_accounts)
+    }
+
+    pub fn refund_arbitrated<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleArbitration<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.refund(ctx.remaining_accounts)
+    }
+
+    pub fn dispute(ctx: Context<Dispute>) -> Result<()> {
+        ctx.accounts.dispute()
+    }
+
+    pub fn arbitrate<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleArbitration<'info>>,
+        payee_percent: u8,
+    ) -> Result<()> {
+        ctx.accounts
+            .arbitrate(payee_percent, ctx.remaining_accounts)
+    }
+
+    pub fn make_milestones<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeMilestones<'info>>,
+        seed: u64,
+        payee: Pubkey,
+        milestones: Vec<MilestoneTerms>,
+    ) -> Result<()> {
+        let total = ctx.accounts.make(seed, payee, milestones, &ctx.bumps)?;
+        ctx.accounts.deposit(total, ctx.remaining_accounts)
+    }
+
+    pub fn release_milestone<'info>(
+        ctx: Context<'_, '_, '_, 'info, ReleaseMilestone<'info>>,
+        index: u8,
+    ) -> Result<()> {
+        ctx.accounts.release(index, ctx.remaining_accounts)
+    }
+
+    pub fn cancel_milestones<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelMilestones<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.cancel(ctx.remaining_accounts)
+    }
+
+    pub fn create_market(ctx: Context<CreateMarket>) -> Result<()> {
+        ctx.accounts.create_market(&ctx.bumps)
+    }
+
+    pub fn list(ctx: Context<List>) -> Result<()> {
+        ctx.accounts.list()
+    }
+
+    pub fn create_nft_metadata(ctx: Context<CreateNftMetadata>, collection: Pubkey) -> Result<()> {
+        ctx.accounts.create_nft_metadata(collection, &ctx.bumps)
+    }
 }
@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+
+/// A payment held for `payee` until one side gives way or `arbiter` settles
+/// a dispute. The funds sit in a vault owned by this account.
+#[account]
+#[derive(InitSpace)]
+pub struct Arbitration {
+    pub seed: u64,
+    pub payer: Pubkey,
+    pub payee: Pubkey,
+    pub arbiter: Pubkey,
+    pub mint: Pubkey,
+    /// Amount held in the vault, net of any transfer fee.
+    pub amount: u64,
+    pub disputed: bool,
+    pub bump: u8,
+}
+
+impl Arbitration {
+    pub const SEED: &'static [u8] = b"arbitration";
+
+    /// Runs `f` with the PDA signer seeds of this arbitration, matching the
+    /// `[b"arbitration", payer, seed.to_le_bytes()]` derivation used in
+    /// `MakeArbitrated`.
+    pub fn with_signer_seeds<R>(&self, f: impl FnOnce(&[&[&[u8]]]) -> R) -> R {
+        let seed_bytes = self.seed.to_le_bytes();
+        let bump = [self.bump];
+
+        f(&[&[Self::SEED, self.payer.as_ref(), &seed_bytes, &bump]])
+    }
+}
@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+
+use crate::error::EscrowError;
+
+/// Keeps `take_basket` within the transaction account and compute limits.
+pub const MAX_BASKET_LEGS: usize = 4;
+
+/// One mint of a basket and the amount of it on offer or asked for.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct BasketLeg {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// An all-or-nothing offer of several mints for several others. Each deposit
+/// is held in a vault owned by the basket, like an `Escrow` vault.
+#[account]
+#[derive(InitSpace)]
+pub struct Basket {
+    pub seed: u64,
+    pub maker: Pubkey,
+    /// Amounts held in escrow, net of any transfer fee.
+    #[max_len(MAX_BASKET_LEGS)]
+    pub deposits: Vec<BasketLeg>,
+    /// Amounts the maker receives, net of any transfer fee.
+    #[max_len(MAX_BASKET_LEGS)]
+    pub asks: Vec<BasketLeg>,
+    pub expires_at: Option<i64>,
+    pub taker: Option<Pubkey>,
+    pub bump: u8,
+}
+
+impl Basket {
+    pub const SEED: &'static [u8] = b"basket";
+
+    /// Runs `f` with the PDA signer seeds of this basket, matching the
+    /// `[b"basket", maker, seed.to_le_bytes()]` derivation used in `MakeBasket`.
+    pub fn with_signer_seeds<R>(&self, f: impl FnOnce(&[&[&[u8]]]) -> R) -> R {
+        let seed_bytes = self.seed.to_le_bytes();
+        let bump = [self.bump];
+
+        f(&[&[Self::SEED, self.maker.as_ref(), &seed_bytes, &bump]])
+    }
+
+    /// Checks that `legs` is non-empty, within `MAX_BASKET_LEGS` and free of
+    /// duplicate mints.
+    pub fn validate_legs(legs: &[BasketLeg]) -> Result<()> {
+        require!(
+            !legs.is_empty() && legs.len() <= MAX_BASKET_LEGS,
+            EscrowError::InvalidBasket
+        );
+
+        for (i, leg) in legs.iter().enumerate() {
+            require!(
+                legs[..i].iter().all(|other| other.mint != leg.mint),
+                EscrowError::InvalidBasket
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    pub fn can_be_taken_by(&self, taker: &Pubkey) -> bool {
+        self.taker.is_none_or(|allowed| allowed == *taker)
+    }
+}
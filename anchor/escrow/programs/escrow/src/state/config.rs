@@ -0,0 +1,51 @@
+use anchor_lang::prelude::*;
+
+use crate::error::EscrowError;
+
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub admin: Pubkey,
+    /// Protocol fee charged once per fill on the `mint_y` amount of the fill.
+    /// The taker pays it on top, so the maker receives their full ask and the
+    /// taker the full payout. Matched offers pay it out of the spread instead.
+    pub fee_bps: u16,
+    pub treasury: Pubkey,
+    pub bump: u8,
+}
+
+impl Config {
+    pub const SEED: &'static [u8] = b"config";
+
+    pub const MAX_FEE_BPS: u16 = 1_000;
+
+    /// Protocol fee owed on `amount`, rounded down.
+    pub fn fee(&self, amount: u64) -> Result<u64> {
+        let fee = (amount as u128)
+            .checked_mul(self.fee_bps as u128)
+            .ok_or(EscrowError::MathOverflow)?
+            / 10_000;
+
+        Ok(fee as u64)
+    }
+
+    /// `amount` with the protocol fee on it added on top.
+    pub fn with_fee(&self, amount: u64) -> Result<u64> {
+        amount
+            .checked_add(self.fee(amount)?)
+            .ok_or(EscrowError::MathOverflow.into())
+    }
+
+    /// Largest amount that still fits in `total` once its protocol fee is
+    /// added on top.
+    pub fn without_fee(&self, total: u64) -> Result<u64> {
+        let mut amount = ((total as u128) * 10_000 / (10_000 + self.fee_bps as u128)) as u64;
+
+        // The fee rounds down, so a slightly larger amount may still fit.
+        while amount < total && self.with_fee(amount + 1)? <= total {
+            amount += 1;
+        }
+
+        Ok(amount)
+    }
+}
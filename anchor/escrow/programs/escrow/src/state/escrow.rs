@@ -0,0 +1,302 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    close_account, CloseAccount, Mint, TokenInterface, TransferChecked,
+};
+use solana_sha256_hasher::hash;
+
+use crate::{
+    error::EscrowError,
+    state::Config,
+    utils::{harvest_withheld_fees, is_native, transfer_checked},
+};
+
+#[account]
+#[derive(InitSpace)]
+pub struct Escrow {
+    pub seed: u64,
+    pub maker: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    /// Amount of `mint_x` held in escrow, net of any transfer fee.
+    pub deposit_amount: u64,
+    /// Amount of `mint_y` the maker receives, net of any transfer fee.
+    pub receive_amount: u64,
+    pub remaining_deposit: u64,
+    pub remaining_receive: u64,
+    pub min_fill_amount: Option<u64>,
+    pub expires_at: Option<i64>,
+    pub taker: Option<Pubkey>,
+    pub auction: Option<DutchAuction>,
+    /// SHA-256 hash of the secret a taker must reveal, for hash-timelocked
+    /// swaps. `expires_at` is the timeout.
+    pub hashlock: Option<[u8; 32]>,
+    /// Set when the position is represented by the receipt token minted at
+    /// `[b"receipt", escrow]`. Proceeds are then held by the escrow and, with
+    /// any refund, go to whoever burns the receipt.
+    pub receipt: bool,
+    /// Whether the offer has an entry in its market. Offers made while the
+    /// market is full stay unlisted until `list` finds room for them.
+    pub is_listed: bool,
+    pub bump: u8,
+}
+
+/// Declining price for the whole deposit, falling linearly from
+/// `start_receive_amount` at `start_ts` to `end_receive_amount` at `end_ts`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct DutchAuction {
+    pub start_receive_amount: u64,
+    pub end_receive_amount: u64,
+    pub start_ts: i64,
+    pub end_ts: i64,
+}
+
+impl DutchAuction {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.end_receive_amount > 0
+                && self.end_receive_amount <= self.start_receive_amount
+                && self.start_ts < self.end_ts,
+            EscrowError::InvalidAuction
+        );
+
+        Ok(())
+    }
+
+    /// Receive amount for the whole deposit at `now`. The decay is rounded
+    /// down, so the price never drops below the curve.
+    pub fn receive_amount_at(&self, now: i64) -> u64 {
+        if now <= self.start_ts {
+            return self.start_receive_amount;
+        }
+        if now >= self.end_ts {
+            return self.end_receive_amount;
+        }
+
+        let elapsed = (now - self.start_ts) as u128;
+        let duration = (self.end_ts - self.start_ts) as u128;
+        let range = (self.start_receive_amount - self.end_receive_amount) as u128;
+
+        self.start_receive_amount - (range * elapsed / duration) as u64
+    }
+}
+
+/// Result of `Escrow::match_with`, as `fill` recorded it on each side:
+/// `fill` of `mint_y` received for `payout` of `mint_x` on the first offer,
+/// and `other_fill` of `mint_x` received for `other_payout` of `mint_y` on
+/// the second. Whatever each side pays out beyond what the other receives is
+/// the spread, which goes to the treasury.
+pub struct OfferMatch {
+    pub fill: u64,
+    pub payout: u64,
+    pub other_fill: u64,
+    pub other_payout: u64,
+}
+
+impl Escrow {
+    pub const SEED: &'static [u8] = b"escrow";
+
+    pub const RECEIPT_SEED: &'static [u8] = b"receipt";
+
+    /// Runs `f` with the PDA signer seeds of this escrow, matching the
+    /// `[b"escrow", maker, seed.to_le_bytes()]` derivation used in `Make`.
+    pub fn with_signer_seeds<R>(&self, f: impl FnOnce(&[&[&[u8]]]) -> R) -> R {
+        let seed_bytes = self.seed.to_le_bytes();
+        let bump = [self.bump];
+
+        f(&[&[Self::SEED, self.maker.as_ref(), &seed_bytes, &bump]])
+    }
+
+    /// Pays `amount` of `mint` out of `account`, a token account owned by
+    /// `escrow`, to `to`.
+    pub fn transfer_out<'info>(
+        escrow: &Account<'info, Escrow>,
+        account: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: account,
+            to,
+            mint: mint.to_account_info(),
+            authority: escrow.to_account_info(),
+        };
+
+        escrow.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+            transfer_checked(cpi_ctx, amount, mint.decimals)
+        })
+    }
+
+    /// Closes `account`, an empty token account owned by `escrow`, and sends
+    /// its rent to `destination`. Any withheld transfer fees are harvested
+    /// first, as Token-2022 will not close the account otherwise.
+    pub fn close_token_account<'info>(
+        escrow: &Account<'info, Escrow>,
+        account: AccountInfo<'info>,
+        destination: AccountInfo<'info>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
+    ) -> Result<()> {
+        harvest_withheld_fees(token_program, mint, account.clone())?;
+
+        let cpi_accounts = CloseAccount {
+            account,
+            destination,
+            authority: escrow.to_account_info(),
+        };
+
+        escrow.with_signer_seeds(|signer_seeds| {
+            let cpi_ctx = CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+
+            close_account(cpi_ctx)
+        })
+    }
+
+    /// Records a fill of `fill_amount` of `mint_y` and returns the amount of
+    /// `mint_x` owed to the taker.
+    ///
+    /// The payout is pro-rata to what is left of the offer and rounded down, so
+    /// any remainder stays with the maker until the final fill takes it all.
+    pub fn fill(&mut self, fill_amount: u64) -> Result<u64> {
+        require!(fill_amount > 0, EscrowError::InvalidFillAmount);
+        require!(
+            fill_amount <= self.remaining_receive,
+            EscrowError::FillExceedsRemaining
+        );
+
+        if let Some(min_fill_amount) = self.min_fill_amount {
+            require!(
+                fill_amount >= min_fill_amount || fill_amount == self.remaining_receive,
+                EscrowError::FillBelowMinimum
+            );
+        }
+
+        let payout = (fill_amount as u128)
+            .checked_mul(self.remaining_deposit as u128)
+            .and_then(|amount| amount.checked_div(self.remaining_receive as u128))
+            .and_then(|amount| u64::try_from(amount).ok())
+            .ok_or(EscrowError::MathOverflow)?;
+
+        require!(payout > 0, EscrowError::FillTooSmall);
+
+        self.remaining_deposit -= payout;
+        self.remaining_receive -= fill_amount;
+
+        Ok(payout)
+    }
+
+    /// Fills this offer and `other`, which must sell `mint_y` for `mint_x`,
+    /// against each other, each at its own price. Each side receives exactly
+    /// what its offer asks for, and the other side must pay out enough on top
+    /// to cover the protocol fee on that fill.
+    pub fn match_with(&mut self, other: &mut Escrow, config: &Config) -> Result<OfferMatch> {
+        require!(
+            other.mint_x == self.mint_y && other.mint_y == self.mint_x,
+            EscrowError::OffersDoNotCross
+        );
+
+        // What each side's deposit can deliver once the fee comes out of it.
+        let deliverable = config.without_fee(self.remaining_deposit)?;
+        let other_deliverable = config.without_fee(other.remaining_deposit)?;
+
+        // Fill whichever side fits entirely into the other, or both when
+        // neither does.
+        let (fill, other_fill) = if deliverable <= other.remaining_receive {
+            (self.remaining_receive, deliverable)
+        } else if other_deliverable <= self.remaining_receive {
+            (other_deliverable, other.remaining_receive)
+        } else {
+            (self.remaining_receive, other.remaining_receive)
+        };
+
+        let payout = self.fill(fill)?;
+        let other_payout = other.fill(other_fill)?;
+
+        // Each side must pay out what the other receives plus the fee on it.
+        require!(
+            payout >= config.with_fee(other_fill)? && other_payout >= config.with_fee(fill)?,
+            EscrowError::OffersDoNotCross
+        );
+
+        Ok(OfferMatch {
+            fill,
+            payout,
+            other_fill,
+            other_payout,
+        })
+    }
+
+    /// Moves `remaining_receive` onto the auction curve at `now`, scaled to
+    /// what is left of the deposit and rounded up in the maker's favour.
+    pub fn reprice(&mut self, now: i64) -> Result<()> {
+        let Some(auction) = self.auction else {
+            return Ok(());
+        };
+
+        let remaining_receive = (auction.receive_amount_at(now) as u128)
+            .checked_mul(self.remaining_deposit as u128)
+            .and_then(|amount| amount.checked_add(self.deposit_amount as u128 - 1))
+            .and_then(|amount| amount.checked_div(self.deposit_amount as u128))
+            .and_then(|amount| u64::try_from(amount).ok())
+            .ok_or(EscrowError::MathOverflow)?;
+
+        self.remaining_receive = remaining_receive;
+
+        Ok(())
+    }
+
+    pub fn is_filled(&self) -> bool {
+        self.remaining_receive == 0
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// Native SOL deposits are held as lamports on the escrow account itself.
+    pub fn is_native_x(&self) -> bool {
+        is_native(&self.mint_x)
+    }
+
+    pub fn is_native_y(&self) -> bool {
+        is_native(&self.mint_y)
+    }
+
+    /// Hash-timelocked offers can only be taken with the secret behind the
+    /// hashlock.
+    pub fn unlock(&self, preimage: Option<[u8; 32]>) -> Result<()> {
+        let Some(hashlock) = self.hashlock else {
+            return Ok(());
+        };
+
+        require!(
+            preimage.is_some_and(|preimage| hash(&preimage).to_bytes() == hashlock),
+            EscrowError::InvalidPreimage
+        );
+
+        Ok(())
+    }
+
+    /// Hash-timelocked offers stay locked for the taker until the timeout.
+    pub fn can_be_withdrawn(&self, now: i64) -> bool {
+        self.hashlock.is_none() || self.is_expired(now)
+    }
+
+    /// Private offers can only be taken by their designated taker.
+    pub fn can_be_taken_by(&self, taker: &Pubkey) -> bool {
+        self.taker.is_none_or(|allowed| allowed == *taker)
+    }
+}
@@ -0,0 +1,20 @@
+use anchor_lang::prelude::*;
+
+/// Marks a signed order's nonce as spent, by a fill or by the maker
+/// cancelling it. Creating it fails if the nonce was already spent, so a
+/// signed order can only ever be filled once. Once the order has expired it
+/// can no longer be filled anyway, and the account can be closed to return
+/// its rent to `payer`.
+#[account]
+#[derive(InitSpace)]
+pub struct FilledOrder {
+    pub maker: Pubkey,
+    pub nonce: u64,
+    pub payer: Pubkey,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+impl FilledOrder {
+    pub const SEED: &'static [u8] = b"order";
+}
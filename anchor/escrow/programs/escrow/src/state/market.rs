@@ -0,0 +1,95 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Escrow;
+
+/// Upper bound on open offers per market, keeping the account under the
+/// 10 KiB limit for accounts created through CPI.
+pub const MAX_MARKET_ENTRIES: usize = 128;
+
+/// An open offer as listed in its market. The price is
+/// `remaining_receive / remaining_deposit`, kept as the two amounts so it
+/// stays exact. Entries only change when the offer does, so for Dutch-auction
+/// offers they hold the price as of the last fill, and clients should reprice
+/// those from the auction terms on the escrow.
+#[zero_copy]
+pub struct MarketEntry {
+    pub escrow: Pubkey,
+    pub remaining_deposit: u64,
+    pub remaining_receive: u64,
+}
+
+/// Index of the open offers selling `mint_x` for `mint_y`, so clients can
+/// list them with a single account read.
+#[account(zero_copy)]
+pub struct Market {
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub len: u32,
+    pub bump: u8,
+    pub _padding: [u8; 3],
+    pub entries: [MarketEntry; MAX_MARKET_ENTRIES],
+}
+
+impl Market {
+    pub const SEED: &'static [u8] = b"market";
+
+    pub fn entries(&self) -> &[MarketEntry] {
+        &self.entries[..self.len as usize]
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len as usize == MAX_MARKET_ENTRIES
+    }
+
+    /// Lists `escrow` if there is room and records on it whether it was. A
+    /// full market leaves the offer unlisted instead of failing, so spam
+    /// cannot block new offers; unlisted offers can still be taken by address
+    /// and listed later with `list`.
+    pub fn insert(&mut self, key: Pubkey, escrow: &mut Escrow) {
+        if self.is_full() {
+            return;
+        }
+
+        let len = self.len as usize;
+        self.entries[len] = MarketEntry {
+            escrow: key,
+            remaining_deposit: escrow.remaining_deposit,
+            remaining_receive: escrow.remaining_receive,
+        };
+        self.len += 1;
+        escrow.is_listed = true;
+    }
+
+    /// Refreshes the entry for `escrow` after a fill or update, dropping it
+    /// once the offer is filled.
+    pub fn sync(&mut self, key: Pubkey, escrow: &mut Escrow) {
+        if !escrow.is_listed {
+            return;
+        }
+
+        if escrow.is_filled() {
+            escrow.is_listed = false;
+            return self.remove(key);
+        }
+
+        if let Some(index) = self.position(key) {
+            let entry = &mut self.entries[index];
+            entry.remaining_deposit = escrow.remaining_deposit;
+            entry.remaining_receive = escrow.remaining_receive;
+        }
+    }
+
+    /// Drops the entry for `key`, if the offer was listed.
+    pub fn remove(&mut self, key: Pubkey) {
+        if let Some(index) = self.position(key) {
+            let last = self.len as usize - 1;
+
+            self.entries[index] = self.entries[last];
+            self.len -= 1;
+        }
+    }
+
+    fn position(&self, key: Pubkey) -> Option<usize> {
+        self.entries().iter().position(|entry| entry.escrow == key)
+    }
+}
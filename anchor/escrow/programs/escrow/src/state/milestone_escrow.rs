@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+
+/// Keeps the account small enough to fit comfortably in a transaction.
+pub const MAX_MILESTONES: usize = 8;
+
+/// A stage of a milestone escrow as requested by the payer.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MilestoneTerms {
+    pub amount: u64,
+    /// Earliest time the payer can release this milestone, if any.
+    pub unlock_at: Option<i64>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Milestone {
+    pub amount: u64,
+    pub unlock_at: Option<i64>,
+    pub released: bool,
+}
+
+impl Milestone {
+    pub fn is_unlocked(&self, now: i64) -> bool {
+        self.unlock_at.is_none_or(|unlock_at| now >= unlock_at)
+    }
+}
+
+/// A payment to `payee` released in stages, each approved by `payer`. The
+/// funds sit in a vault owned by this account.
+#[account]
+#[derive(InitSpace)]
+pub struct MilestoneEscrow {
+    pub seed: u64,
+    pub payer: Pubkey,
+    pub payee: Pubkey,
+    pub mint: Pubkey,
+    #[max_len(MAX_MILESTONES)]
+    pub milestones: Vec<Milestone>,
+    pub bump: u8,
+}
+
+impl MilestoneEscrow {
+    pub const SEED: &'static [u8] = b"milestones";
+
+    /// Runs `f` with the PDA signer seeds of this escrow, matching the
+    /// `[b"milestones", payer, seed.to_le_bytes()]` derivation used in
+    /// `MakeMilestones`.
+    pub fn with_signer_seeds<R>(&self, f: impl FnOnce(&[&[&[u8]]]) -> R) -> R {
+        let seed_bytes = self.seed.to_le_bytes();
+        let bump = [self.bump];
+
+        f(&[&[Self::SEED, self.payer.as_ref(), &seed_bytes, &bump]])
+    }
+}
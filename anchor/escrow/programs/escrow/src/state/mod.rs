@@ -1,13 +1,17 @@
-use anchor_lang::prelude::*;
+pub mod arbitration;
+pub mod basket;
+pub mod config;
+pub mod escrow;
+pub mod filled_order;
+pub mod market;
+pub mod milestone_escrow;
+pub mod nft_metadata;
 
-#[account]
-#[derive(InitSpace)]
-pub struct Escrow {
-    pub seed: u64,
-    pub maker: Pubkey,
-    pub mint_x: Pubkey,
-    pub mint_y: Pubkey,
-    pub deposit_amount: u64,
-    pub receive_amount: u64,
-    pub bump: u8,
-}
+pub use arbitration::*;
+pub use basket::*;
+pub use config::*;
+pub use escrow::*;
+pub use filled_order::*;
+pub use market::*;
+pub use milestone_escrow::*;
+pub use nft_metadata::*;
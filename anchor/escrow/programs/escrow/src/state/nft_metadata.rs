@@ -0,0 +1,15 @@
+use anchor_lang::prelude::*;
+
+/// Stand-in for token metadata, recording the collection an NFT belongs to
+/// so offers can require one.
+#[account]
+#[derive(InitSpace)]
+pub struct NftMetadata {
+    pub mint: Pubkey,
+    pub collection: Pubkey,
+    pub bump: u8,
+}
+
+impl NftMetadata {
+    pub const SEED: &'static [u8] = b"metadata";
+}
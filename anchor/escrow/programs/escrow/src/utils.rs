@@ -0,0 +1,255 @@
+use anchor_lang::{prelude::*, solana_program::pubkey::PUBKEY_BYTES};
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token::spl_token::native_mint,
+    token_2022::spl_token_2022::{
+        extension::{
+            transfer_fee::TransferFeeConfig, BaseStateWithExtensions, ExtensionType,
+            StateWithExtensions,
+        },
+        onchain::invoke_transfer_checked,
+        state::Mint as MintState,
+    },
+    token_interface::{
+        harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint, Mint, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use solana_instructions_sysvar::get_instruction_relative;
+use solana_sdk_ids::ed25519_program;
+
+use crate::{error::EscrowError, state::NftMetadata};
+
+/// Signature count, padding and one set of signature offsets.
+const ED25519_HEADER_LEN: usize = 16;
+
+/// Mint extensions that cannot lock tokens inside the vault. Anything else,
+/// such as non-transferable or pausable mints, is rejected when an offer is made.
+const SUPPORTED_MINT_EXTENSIONS: &[ExtensionType] = &[
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::TransferHook,
+    ExtensionType::MintCloseAuthority,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+    ExtensionType::TokenGroupMember,
+];
+
+/// Offers use the SPL Token native mint to stand for plain SOL. SOL legs move
+/// lamports directly instead of going through token accounts.
+pub fn is_native(mint: &Pubkey) -> bool {
+    *mint == native_mint::ID
+}
+
+/// Unwraps a token account that is optional only because of native SOL legs.
+pub fn required<T>(account: &Option<T>) -> Result<&T> {
+    account
+        .as_ref()
+        .ok_or(EscrowError::InvalidTokenAccounts.into())
+}
+
+pub fn validate_mint(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let info = mint.to_account_info();
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<MintState>::unpack(&data)?;
+
+    for extension in state.get_extension_types()? {
+        require!(
+            SUPPORTED_MINT_EXTENSIONS.contains(&extension),
+            EscrowError::UnsupportedMintExtension
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks that `mint` is an NFT whose supply can never grow past one and,
+/// when `collection` is set, that `metadata` places it in that collection.
+pub fn validate_nft(
+    mint: &InterfaceAccount<Mint>,
+    metadata: Option<&Account<NftMetadata>>,
+    collection: Option<Pubkey>,
+) -> Result<()> {
+    require!(
+        mint.decimals == 0 && mint.supply == 1 && mint.mint_authority.is_none(),
+        EscrowError::InvalidNft
+    );
+
+    if let Some(collection) = collection {
+        let metadata = metadata.ok_or(EscrowError::CollectionMismatch)?;
+        require!(
+            metadata.mint == mint.key() && metadata.collection == collection,
+            EscrowError::CollectionMismatch
+        );
+    }
+
+    Ok(())
+}
+
+/// Basket legs share `remaining_accounts`, which leaves no room for the extra
+/// accounts a transfer hook needs.
+pub fn validate_basket_mint(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    validate_mint(mint)?;
+
+    let info = mint.to_account_info();
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<MintState>::unpack(&data)?;
+
+    require!(
+        !state
+            .get_extension_types()?
+            .contains(&ExtensionType::TransferHook),
+        EscrowError::UnsupportedMintExtension
+    );
+
+    Ok(())
+}
+
+/// Checks that `account` is the associated token account of `authority` for
+/// `mint`, for token accounts passed through `remaining_accounts`.
+pub fn require_ata(
+    account: &AccountInfo,
+    authority: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<()> {
+    require_keys_eq!(
+        account.key(),
+        get_associated_token_address_with_program_id(authority, mint, token_program),
+        EscrowError::InvalidTokenAccounts
+    );
+
+    Ok(())
+}
+
+/// Checks that the instruction just before the current one is an Ed25519
+/// program instruction verifying a single signature by `signer` over
+/// `message`. The Ed25519 program fails the whole transaction on a bad
+/// signature, so only the data it verified needs checking here.
+pub fn verify_ed25519_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let instruction =
+        get_instruction_relative(-1, instructions).map_err(|_| EscrowError::InvalidSignature)?;
+    require_keys_eq!(
+        instruction.program_id,
+        ed25519_program::ID,
+        EscrowError::InvalidSignature
+    );
+
+    // One signature, followed by its 14-byte offsets header.
+    let data = &instruction.data;
+    require!(
+        data.len() >= ED25519_HEADER_LEN && data[0] == 1,
+        EscrowError::InvalidSignature
+    );
+
+    let read = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let [signature_ix, public_key_offset, public_key_ix, message_offset, message_size, message_ix] =
+        [4, 6, 8, 10, 12, 14].map(read);
+
+    // The signed data must live in the Ed25519 instruction itself, otherwise
+    // the bytes read below are not the ones that were verified.
+    require!(
+        [signature_ix, public_key_ix, message_ix]
+            .iter()
+            .all(|&ix| ix == u16::MAX),
+        EscrowError::InvalidSignature
+    );
+
+    let public_key_offset = public_key_offset as usize;
+    let message_offset = message_offset as usize;
+
+    let public_key = data.get(public_key_offset..public_key_offset + PUBKEY_BYTES);
+    let signed_message = data.get(message_offset..message_offset + message_size as usize);
+
+    require!(
+        public_key == Some(signer.as_ref()) && signed_message == Some(message),
+        EscrowError::InvalidSignature
+    );
+
+    Ok(())
+}
+
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let info = mint.to_account_info();
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<MintState>::unpack(&data)?;
+
+    Ok(state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+/// Whether transfers of `mint` can be charged a Token-2022 transfer fee.
+pub fn has_transfer_fee(mint: &InterfaceAccount<Mint>) -> Result<bool> {
+    Ok(transfer_fee_config(mint)?.is_some())
+}
+
+/// Fee withheld by the token program when `amount` of `mint` is transferred.
+pub fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match transfer_fee_config(mint)? {
+        Some(config) => config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(EscrowError::MathOverflow.into()),
+        None => Ok(0),
+    }
+}
+
+/// Amount of `mint` to send so that exactly `amount` arrives after fees.
+pub fn amount_with_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match transfer_fee_config(mint)? {
+        Some(config) => config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+            .and_then(|fee| amount.checked_add(fee))
+            .ok_or(EscrowError::MathOverflow.into()),
+        None => Ok(amount),
+    }
+}
+
+/// Drop-in replacement for `token_interface::transfer_checked` that also
+/// resolves transfer-hook accounts from `ctx.remaining_accounts`.
+pub fn transfer_checked<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    invoke_transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority,
+        &ctx.remaining_accounts,
+        amount,
+        decimals,
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Moves fees withheld in `account` back to the mint, which Token-2022
+/// requires before a token account holding withheld fees can be closed.
+pub fn harvest_withheld_fees<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    mint: &InterfaceAccount<'info, Mint>,
+    account: AccountInfo<'info>,
+) -> Result<()> {
+    if transfer_fee_config(mint)?.is_none() {
+        return Ok(());
+    }
+
+    let cpi_ctx = CpiContext::new(
+        token_program.to_account_info(),
+        HarvestWithheldTokensToMint {
+            token_program_id: token_program.to_account_info(),
+            mint: mint.to_account_info(),
+        },
+    );
+
+    harvest_withheld_tokens_to_mint(cpi_ctx, vec![account])
+}
@@ -0,0 +1,281 @@
+import * as anchor from "@coral-xyz/anchor";
+import { BN, Program } from "@coral-xyz/anchor";
+import {
+  AccountLayout,
+  MINT_SIZE,
+  TOKEN_PROGRAM_ID,
+  createAssociatedTokenAccountIdempotentInstruction,
+  createInitializeMint2Instruction,
+  createMintToInstruction,
+  getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
+import { Clock, FailedTransactionMetadata, LiteSVM } from "litesvm";
+import { Escrow } from "../target/types/escrow";
+import { assert } from "chai";
+
+// Runs in an in-process SVM so the clock can be warped across the auction.
+describe("dutch auction", () => {
+  const program = anchor.workspace.Escrow as Program<Escrow>;
+
+  const svm = new LiteSVM();
+  const maker = anchor.web3.Keypair.generate();
+  const taker = anchor.web3.Keypair.generate();
+  const treasury = anchor.web3.Keypair.generate();
+  const mintX = anchor.web3.Keypair.generate();
+  const mintY = anchor.web3.Keypair.generate();
+
+  const makerAtaX = getAssociatedTokenAddressSync(
+    mintX.publicKey,
+    maker.publicKey
+  );
+  const takerAtaX = getAssociatedTokenAddressSync(
+    mintX.publicKey,
+    taker.publicKey
+  );
+  const takerAtaY = getAssociatedTokenAddressSync(
+    mintY.publicKey,
+    taker.publicKey
+  );
+
+  const pda = (...seeds: Buffer[]) =>
+    anchor.web3.PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+  const seed = new BN(1);
+  const escrow = pda(
+    Buffer.from("escrow"),
+    maker.publicKey.toBuffer(),
+    seed.toArrayLike(Buffer, "le", 8)
+  );
+  const market = pda(
+    Buffer.from("market"),
+    mintX.publicKey.toBuffer(),
+    mintY.publicKey.toBuffer()
+  );
+  const [config, configBump] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+
+  const send = (
+    instructions: anchor.web3.TransactionInstruction[],
+    signers: anchor.web3.Keypair[]
+  ) => {
+    const tx = new anchor.web3.Transaction().add(...instructions);
+    tx.recentBlockhash = svm.latestBlockhash();
+    tx.feePayer = signers[0].publicKey;
+    tx.sign(...signers);
+
+    const result = svm.sendTransaction(tx);
+    if (result instanceof FailedTransactionMetadata) {
+      throw new Error(result.meta().logs().join("\n"));
+    }
+  };
+
+  const warpTo = (unixTimestamp: number) => {
+    const clock = svm.getClock();
+    svm.setClock(
+      new Clock(
+        clock.slot,
+        clock.epochStartTimestamp,
+        clock.epoch,
+        clock.leaderScheduleEpoch,
+        BigInt(unixTimestamp)
+      )
+    );
+  };
+
+  const balance = (ata: anchor.web3.PublicKey) =>
+    Number(AccountLayout.decode(svm.getAccount(ata).data).amount);
+
+  const fetchEscrow = () =>
+    program.coder.accounts.decode(
+      "escrow",
+      Buffer.from(svm.getAccount(escrow).data)
+    );
+
+  const take = async (fillAmount: number) =>
+    send(
+      [
+        await program.methods
+          .take(new BN(fillAmount), null)
+          .accountsPartial({
+            taker: taker.publicKey,
+            maker: maker.publicKey,
+            mintX: mintX.publicKey,
+            mintY: mintY.publicKey,
+            escrow,
+            market,
+            config,
+            treasury: treasury.publicKey,
+            treasuryAtaY: null,
+            proceeds: null,
+            tokenProgramX: TOKEN_PROGRAM_ID,
+            tokenProgramY: TOKEN_PROGRAM_ID,
+          })
+          .instruction(),
+      ],
+      [taker]
+    );
+
+  let startTs: number;
+
+  before(async () => {
+    svm.addProgramFromFile(program.programId, "target/deploy/escrow.so");
+    svm.airdrop(maker.publicKey, BigInt(10 * anchor.web3.LAMPORTS_PER_SOL));
+    svm.airdrop(taker.publicKey, BigInt(10 * anchor.web3.LAMPORTS_PER_SOL));
+
+    const mintRent = Number(
+      svm.minimumBalanceForRentExemption(BigInt(MINT_SIZE))
+    );
+    send(
+      [
+        ...[mintX, mintY].flatMap((mint) => [
+          anchor.web3.SystemProgram.createAccount({
+            fromPubkey: maker.publicKey,
+            newAccountPubkey: mint.publicKey,
+            lamports: mintRent,
+            space: MINT_SIZE,
+            programId: TOKEN_PROGRAM_ID,
+          }),
+          createInitializeMint2Instruction(
+            mint.publicKey,
+            6,
+            maker.publicKey,
+            null
+          ),
+        ]),
+        createAssociatedTokenAccountIdempotentInstruction(
+          maker.publicKey,
+          makerAtaX,
+          maker.publicKey,
+          mintX.publicKey
+        ),
+        createAssociatedTokenAccountIdempotentInstruction(
+          maker.publicKey,
+          takerAtaX,
+          taker.publicKey,
+          mintX.publicKey
+        ),
+        createAssociatedTokenAccountIdempotentInstruction(
+          maker.publicKey,
+          takerAtaY,
+          taker.publicKey,
+          mintY.publicKey
+        ),
+        createMintToInstruction(
+          mintX.publicKey,
+          makerAtaX,
+          maker.publicKey,
+          1_000
+        ),
+        createMintToInstruction(
+          mintY.publicKey,
+          takerAtaY,
+          maker.publicKey,
+          10_000_000
+        ),
+      ],
+      [maker, mintX, mintY]
+    );
+
+    // The program is not deployed as upgradeable here, so the config that
+    // initialize_config would create is written directly.
+    const configData = await program.coder.accounts.encode("config", {
+      admin: maker.publicKey,
+      feeBps: 0,
+      treasury: treasury.publicKey,
+      bump: configBump,
+    });
+    svm.setAccount(config, {
+      lamports: Number(
+        svm.minimumBalanceForRentExemption(BigInt(configData.length))
+      ),
+      data: configData,
+      owner: program.programId,
+      executable: false,
+    });
+
+    startTs = Number(svm.getClock().unixTimestamp) + 100;
+
+    send(
+      [
+        await program.methods
+          .createMarket()
+          .accountsPartial({
+            payer: maker.publicKey,
+            mintX: mintX.publicKey,
+            mintY: mintY.publicKey,
+          })
+          .instruction(),
+        await program.methods
+          .make(seed, new BN(1_000), new BN(1_000_000), {
+            minFillAmount: null,
+            expiresAt: null,
+            taker: null,
+            nftX: null,
+            nftY: null,
+            auction: {
+              startReceiveAmount: new BN(1_000_000),
+              endReceiveAmount: new BN(100_000),
+              startTs: new BN(startTs),
+              endTs: new BN(startTs + 1_000),
+            },
+            hashlock: null,
+            receipt: false,
+          })
+          .accountsPartial({
+            maker: maker.publicKey,
+            mintX: mintX.publicKey,
+            mintY: mintY.publicKey,
+            escrow,
+            market,
+            metadataX: null,
+            metadataY: null,
+            receiptMint: null,
+            makerReceiptAta: null,
+            proceeds: null,
+            tokenProgramX: TOKEN_PROGRAM_ID,
+            tokenProgramY: TOKEN_PROGRAM_ID,
+          })
+          .instruction(),
+      ],
+      [maker]
+    );
+  });
+
+  it("Charges the start price before the auction begins", async () => {
+    warpTo(startTs - 10);
+
+    await take(10_000);
+
+    // 10_000 of the 1_000_000 asked for all 1_000 tokens buys 10 of them.
+    assert.equal(balance(takerAtaX), 10);
+
+    const escrowAccount = fetchEscrow();
+    assert.equal(escrowAccount.remainingDeposit.toNumber(), 990);
+    assert.equal(escrowAccount.remainingReceive.toNumber(), 990_000);
+  });
+
+  it("Interpolates the price halfway through the auction", async () => {
+    warpTo(startTs + 500);
+
+    // The whole deposit now costs 550_000, so the 990 left cost 544_500.
+    await take(55_000);
+
+    assert.equal(balance(takerAtaX), 110);
+
+    const escrowAccount = fetchEscrow();
+    assert.equal(escrowAccount.remainingDeposit.toNumber(), 890);
+    assert.equal(escrowAccount.remainingReceive.toNumber(), 489_500);
+  });
+
+  it("Holds the end price once the auction is over", async () => {
+    warpTo(startTs + 5_000);
+
+    // 100_000 for the whole deposit leaves 89_000 for the last 890.
+    await take(89_000);
+
+    assert.equal(balance(takerAtaX), 1_000);
+    assert.isNull(svm.getAccount(escrow));
+  });
+});
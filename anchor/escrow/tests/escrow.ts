@@ -1 +1,2728 @@
-// todo
+import * as anchor from "@coral-xyz/anchor";
+import { BN, Program } from "@coral-xyz/anchor";
+import {
+  AuthorityType,
+  ExtensionType,
+  NATIVE_MINT,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  approve,
+  createInitializeMintInstruction,
+  createInitializeNonTransferableMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  setAuthority,
+  transfer,
+} from "@solana/spl-token";
+import { Escrow } from "../target/types/escrow";
+import { assert } from "chai";
+import { createHash, randomBytes } from "crypto";
+
+describe("escrow", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Escrow as Program<Escrow>;
+  const connection = provider.connection;
+  const payer = (provider.wallet as anchor.Wallet).payer;
+
+  const maker = anchor.web3.Keypair.generate();
+  const taker = anchor.web3.Keypair.generate();
+  const treasury = anchor.web3.Keypair.generate();
+
+  const [programData] = anchor.web3.PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  );
+
+  let mintX: anchor.web3.PublicKey;
+  let mintY: anchor.web3.PublicKey;
+  let makerAtaX: anchor.web3.PublicKey;
+  let makerAtaY: anchor.web3.PublicKey;
+  let takerAtaX: anchor.web3.PublicKey;
+  let takerAtaY: anchor.web3.PublicKey;
+
+  const escrowPda = (seed: BN) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("escrow"),
+        maker.publicKey.toBuffer(),
+        seed.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+  const vaultFor = (escrow: anchor.web3.PublicKey) =>
+    getAssociatedTokenAddressSync(mintX, escrow, true);
+
+  const balance = async (ata: anchor.web3.PublicKey) =>
+    Number((await getAccount(connection, ata)).amount);
+
+  type MakeOptions = {
+    minFillAmount?: number;
+    expiresAt?: number;
+    taker?: anchor.web3.PublicKey;
+    nftX?: { collection: anchor.web3.PublicKey | null };
+    nftY?: { collection: anchor.web3.PublicKey | null };
+    hashlock?: number[];
+    receipt?: boolean;
+  };
+
+  const makeOptions = ({
+    minFillAmount,
+    expiresAt,
+    taker,
+    nftX,
+    nftY,
+    hashlock,
+    receipt,
+  }: MakeOptions) => ({
+    minFillAmount: minFillAmount === undefined ? null : new BN(minFillAmount),
+    expiresAt: expiresAt === undefined ? null : new BN(expiresAt),
+    taker: taker ?? null,
+    nftX: nftX ?? null,
+    nftY: nftY ?? null,
+    auction: null,
+    hashlock: hashlock ?? null,
+    receipt: receipt ?? false,
+  });
+
+  // Receipt accounts are derived automatically, so offers without a receipt
+  // have to leave them out explicitly.
+  const noReceipt = {
+    receiptMint: null,
+    makerReceiptAta: null,
+    proceeds: null,
+  };
+
+  const make = (
+    seed: BN,
+    depositAmount: number,
+    receiveAmount: number,
+    options: MakeOptions = {}
+  ) =>
+    program.methods
+      .make(
+        seed,
+        new BN(depositAmount),
+        new BN(receiveAmount),
+        makeOptions(options)
+      )
+      .accountsPartial({
+        maker: maker.publicKey,
+        mintX,
+        mintY,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ...noReceipt,
+      })
+      .signers([maker])
+      .rpc();
+
+  const marketPda = (x: anchor.web3.PublicKey, y: anchor.web3.PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("market"), x.toBuffer(), y.toBuffer()],
+      program.programId
+    )[0];
+
+  const createMarket = (x: anchor.web3.PublicKey, y: anchor.web3.PublicKey) =>
+    program.methods
+      .createMarket()
+      .accountsPartial({ payer: provider.publicKey, mintX: x, mintY: y })
+      .rpc();
+
+  const take = (
+    escrow: anchor.web3.PublicKey,
+    fillAmount: number,
+    preimage: number[] | null = null
+  ) =>
+    program.methods
+      .take(new BN(fillAmount), preimage)
+      .accountsPartial({
+        taker: taker.publicKey,
+        maker: maker.publicKey,
+        mintX,
+        mintY,
+        escrow,
+        treasury: treasury.publicKey,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        proceeds: null,
+      })
+      .signers([taker])
+      .rpc();
+
+  const expire = (escrow: anchor.web3.PublicKey) =>
+    program.methods
+      .expire()
+      .accountsPartial({
+        caller: taker.publicKey,
+        maker: maker.publicKey,
+        mintX,
+        escrow,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([taker])
+      .rpc();
+
+  // Event CPI payloads follow the 8-byte discriminator of the self-invocation.
+  const cpiEventsOf = async (signature: string) => {
+    await connection.confirmTransaction(signature, "confirmed");
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const keys = tx.transaction.message.staticAccountKeys;
+    return tx.meta.innerInstructions
+      .flatMap((inner) => inner.instructions)
+      .filter((ix) => keys[ix.programIdIndex].equals(program.programId))
+      .map((ix) =>
+        program.coder.events.decode(
+          Buffer.from(
+            anchor.utils.bytes.bs58.decode(ix.data).subarray(8)
+          ).toString("base64")
+        )
+      )
+      .filter((event) => event !== null);
+  };
+
+  const chainTime = async () =>
+    connection.getBlockTime(await connection.getSlot("confirmed"));
+
+  const sleep = (ms: number) =>
+    new Promise((resolve) => setTimeout(resolve, ms));
+
+  const refund = (escrow: anchor.web3.PublicKey) =>
+    program.methods
+      .refund()
+      .accountsPartial({
+        maker: maker.publicKey,
+        mintX,
+        escrow,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([maker])
+      .rpc();
+
+  before(async () => {
+    for (const user of [maker, taker]) {
+      const sig = await connection.requestAirdrop(
+        user.publicKey,
+        10 * anchor.web3.LAMPORTS_PER_SOL
+      );
+      await connection.confirmTransaction(sig);
+    }
+
+    mintX = await createMint(connection, payer, payer.publicKey, null, 6);
+    mintY = await createMint(connection, payer, payer.publicKey, null, 6);
+
+    makerAtaX = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mintX,
+        maker.publicKey
+      )
+    ).address;
+    makerAtaY = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mintY,
+        maker.publicKey
+      )
+    ).address;
+    takerAtaX = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mintX,
+        taker.publicKey
+      )
+    ).address;
+    takerAtaY = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mintY,
+        taker.publicKey
+      )
+    ).address;
+
+    await mintTo(connection, payer, mintX, makerAtaX, payer, 1_000_000_000);
+    await mintTo(connection, payer, mintY, takerAtaY, payer, 1_000_000_000);
+
+    await createMarket(mintX, mintY);
+    await createMarket(NATIVE_MINT, mintY);
+    await createMarket(mintX, NATIVE_MINT);
+
+    // Fees start disabled so balances in most tests are easy to follow.
+    await program.methods
+      .initializeConfig(0, treasury.publicKey)
+      .accountsPartial({
+        admin: provider.publicKey,
+        programData,
+      })
+      .rpc();
+  });
+
+  it("Makes an offer and deposits into the vault", async () => {
+    const seed = new BN(1);
+    const before = await balance(makerAtaX);
+
+    await make(seed, 10_000, 20_000);
+
+    const escrow = escrowPda(seed);
+    const escrowAccount = await program.account.escrow.fetch(escrow);
+
+    assert.isTrue(escrowAccount.maker.equals(maker.publicKey));
+    assert.equal(escrowAccount.depositAmount.toNumber(), 10_000);
+    assert.equal(escrowAccount.receiveAmount.toNumber(), 20_000);
+    assert.equal(await balance(vaultFor(escrow)), 10_000);
+    assert.equal(await balance(makerAtaX), before - 10_000);
+  });
+
+  it("Rejects a zero deposit amount", async () => {
+    try {
+      await make(new BN(2), 0, 20_000);
+      assert.fail("make should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidDepositAmount");
+    }
+  });
+
+  it("Rejects a zero receive amount", async () => {
+    try {
+      await make(new BN(3), 10_000, 0);
+      assert.fail("make should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidReceiveAmount");
+    }
+  });
+
+  it("Refunds an open offer to the maker", async () => {
+    const seed = new BN(4);
+    const before = await balance(makerAtaX);
+
+    await make(seed, 10_000, 20_000);
+
+    const escrow = escrowPda(seed);
+    const vault = vaultFor(escrow);
+
+    await refund(escrow);
+
+    assert.equal(await balance(makerAtaX), before);
+    assert.isNull(await connection.getAccountInfo(vault));
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Only lets the maker refund", async () => {
+    const seed = new BN(5);
+
+    await make(seed, 10_000, 20_000);
+
+    try {
+      await program.methods
+        .refund()
+        .accountsPartial({
+          maker: taker.publicKey,
+          mintX,
+          escrow: escrowPda(seed),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([taker])
+        .rpc();
+      assert.fail("refund should have failed");
+    } catch (err) {
+      assert.instanceOf(err, anchor.AnchorError);
+    }
+  });
+
+  // Signed CPIs must use the same little-endian seed bytes as the PDA
+  // derivation in `make`, so exercise seeds that are not byte palindromes.
+  const seeds = [
+    new BN(0),
+    new BN(256),
+    new BN("0102030405060708", 16),
+    new BN("8000000000000000", 16),
+    new BN("ffffffffffffffff", 16),
+  ];
+
+  for (const seed of seeds) {
+    it(`Takes an escrow made with seed ${seed.toString(16)}`, async () => {
+      const escrow = escrowPda(seed);
+      const makerYBefore = await balance(makerAtaY);
+      const takerXBefore = await balance(takerAtaX);
+
+      await make(seed, 10_000, 20_000);
+      await take(escrow, 20_000);
+
+      assert.equal(await balance(makerAtaY), makerYBefore + 20_000);
+      assert.equal(await balance(takerAtaX), takerXBefore + 10_000);
+      assert.isNull(await connection.getAccountInfo(vaultFor(escrow)));
+      assert.isNull(await connection.getAccountInfo(escrow));
+    });
+  }
+
+  it("Returns the escrow and vault rent to the maker on take", async () => {
+    const seed = new BN(6);
+    const escrow = escrowPda(seed);
+    const vault = vaultFor(escrow);
+
+    await make(seed, 10_000, 20_000);
+
+    const rent =
+      (await connection.getBalance(escrow)) +
+      (await connection.getBalance(vault));
+    const makerBefore = await connection.getBalance(maker.publicKey);
+
+    await take(escrow, 20_000);
+
+    assert.isNull(await connection.getAccountInfo(escrow));
+    assert.isNull(await connection.getAccountInfo(vault));
+    assert.equal(
+      await connection.getBalance(maker.publicKey),
+      makerBefore + rent
+    );
+  });
+
+  it("Completes make and take between two distinct mints", async () => {
+    // A fresh maker with no `mint_b` token account, which take has to create.
+    const seller = anchor.web3.Keypair.generate();
+    const sig = await connection.requestAirdrop(
+      seller.publicKey,
+      10 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await connection.confirmTransaction(sig);
+
+    const mintA = await createMint(connection, payer, payer.publicKey, null, 9);
+    const mintB = await createMint(connection, payer, payer.publicKey, null, 2);
+    await createMarket(mintA, mintB);
+
+    const sellerAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mintA,
+        seller.publicKey
+      )
+    ).address;
+    const buyerAtaA = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mintA,
+        taker.publicKey
+      )
+    ).address;
+    const buyerAtaB = (
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mintB,
+        taker.publicKey
+      )
+    ).address;
+    const sellerAtaB = getAssociatedTokenAddressSync(mintB, seller.publicKey);
+
+    await mintTo(connection, payer, mintA, sellerAtaA, payer, 5_000_000_000);
+    await mintTo(connection, payer, mintB, buyerAtaB, payer, 75_000);
+
+    const seed = new BN(7);
+    const [escrow] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("escrow"),
+        seller.publicKey.toBuffer(),
+        seed.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const vault = getAssociatedTokenAddressSync(mintA, escrow, true);
+
+    await program.methods
+      .make(seed, new BN(5_000_000_000), new BN(75_000), makeOptions({}))
+      .accountsPartial({
+        maker: seller.publicKey,
+        mintX: mintA,
+        mintY: mintB,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ...noReceipt,
+      })
+      .signers([seller])
+      .rpc();
+
+    assert.equal(await balance(vault), 5_000_000_000);
+    assert.isNull(await connection.getAccountInfo(sellerAtaB));
+
+    await program.methods
+      .take(new BN(75_000), null)
+      .accountsPartial({
+        taker: taker.publicKey,
+        maker: seller.publicKey,
+        mintX: mintA,
+        mintY: mintB,
+        escrow,
+        treasury: treasury.publicKey,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        proceeds: null,
+      })
+      .signers([taker])
+      .rpc();
+
+    assert.equal(await balance(sellerAtaA), 0);
+    assert.equal(await balance(sellerAtaB), 75_000);
+    assert.equal(await balance(buyerAtaA), 5_000_000_000);
+    assert.equal(await balance(buyerAtaB), 0);
+    assert.isNull(await connection.getAccountInfo(vault));
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Refunds an escrow made with a non-palindromic seed", async () => {
+    const seed = new BN("deadbeefcafe", 16);
+    const escrow = escrowPda(seed);
+    const before = await balance(makerAtaX);
+
+    await make(seed, 10_000, 20_000);
+    await refund(escrow);
+
+    assert.equal(await balance(makerAtaX), before);
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Partially fills an offer, rounding in the maker's favor", async () => {
+    const seed = new BN(8);
+    const escrow = escrowPda(seed);
+    const vault = vaultFor(escrow);
+    const makerYBefore = await balance(makerAtaY);
+    const takerXBefore = await balance(takerAtaX);
+
+    // 1_000 X for 3_000 Y, so one Y is worth a third of an X.
+    await make(seed, 1_000, 3_000);
+
+    await take(escrow, 1_000);
+
+    // 1_000 * 1_000 / 3_000 = 333.33, rounded down for the taker.
+    let escrowAccount = await program.account.escrow.fetch(escrow);
+    assert.equal(escrowAccount.remainingDeposit.toNumber(), 667);
+    assert.equal(escrowAccount.remainingReceive.toNumber(), 2_000);
+    assert.equal(await balance(takerAtaX), takerXBefore + 333);
+    assert.equal(await balance(vault), 667);
+
+    await take(escrow, 1_001);
+
+    // 1_001 * 667 / 2_000 = 333.83, rounded down again.
+    escrowAccount = await program.account.escrow.fetch(escrow);
+    assert.equal(escrowAccount.remainingDeposit.toNumber(), 334);
+    assert.equal(escrowAccount.remainingReceive.toNumber(), 999);
+    assert.equal(await balance(takerAtaX), takerXBefore + 666);
+
+    // The final fill takes whatever is left in the vault.
+    await take(escrow, 999);
+
+    assert.equal(await balance(takerAtaX), takerXBefore + 1_000);
+    assert.equal(await balance(makerAtaY), makerYBefore + 3_000);
+    assert.isNull(await connection.getAccountInfo(vault));
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Rejects fills larger than the remaining amount", async () => {
+    const seed = new BN(9);
+
+    await make(seed, 1_000, 3_000);
+
+    try {
+      await take(escrowPda(seed), 3_001);
+      assert.fail("take should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "FillExceedsRemaining");
+    }
+  });
+
+  it("Rejects fills too small to pay out anything", async () => {
+    const seed = new BN(10);
+
+    await make(seed, 1_000, 3_000);
+
+    try {
+      await take(escrowPda(seed), 2);
+      assert.fail("take should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "FillTooSmall");
+    }
+  });
+
+  it("Enforces the minimum fill size except for the final fill", async () => {
+    const seed = new BN(11);
+    const escrow = escrowPda(seed);
+
+    await make(seed, 1_000, 3_000, { minFillAmount: 1_000 });
+
+    try {
+      await take(escrow, 999);
+      assert.fail("take should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "FillBelowMinimum");
+    }
+
+    await take(escrow, 2_500);
+    await take(escrow, 500);
+
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Refunds the remainder of a partially filled offer", async () => {
+    const seed = new BN(12);
+    const escrow = escrowPda(seed);
+    const before = await balance(makerAtaX);
+
+    await make(seed, 1_000, 3_000);
+    await take(escrow, 1_500);
+    await refund(escrow);
+
+    assert.equal(await balance(makerAtaX), before - 500);
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Rejects an expiry in the past", async () => {
+    try {
+      await make(new BN(13), 1_000, 3_000, {
+        expiresAt: (await chainTime()) - 60,
+      });
+      assert.fail("make should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidExpiry");
+    }
+  });
+
+  it("Only allows expired offers to be refunded or expired", async () => {
+    const seed = new BN(14);
+    const escrow = escrowPda(seed);
+    const vault = vaultFor(escrow);
+    const before = await balance(makerAtaX);
+    const expiresAt = (await chainTime()) + 5;
+
+    await make(seed, 1_000, 3_000, { expiresAt });
+
+    // Still open, so nobody can crank it yet but it can be filled.
+    try {
+      await expire(escrow);
+      assert.fail("expire should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "OfferNotExpired");
+    }
+
+    await take(escrow, 300);
+
+    while ((await chainTime()) < expiresAt) {
+      await sleep(500);
+    }
+
+    try {
+      await take(escrow, 300);
+      assert.fail("take should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "OfferExpired");
+    }
+
+    // Anyone can return an expired offer to its maker.
+    await expire(escrow);
+
+    assert.equal(await balance(makerAtaX), before - 100);
+    assert.isNull(await connection.getAccountInfo(vault));
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Only lets the designated taker fill a private offer", async () => {
+    const seed = new BN(15);
+    const escrow = escrowPda(seed);
+    const outsider = anchor.web3.Keypair.generate();
+
+    for (const mint of [mintX, mintY]) {
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mint,
+        outsider.publicKey
+      );
+    }
+
+    await make(seed, 1_000, 3_000, { taker: taker.publicKey });
+
+    try {
+      await program.methods
+        .take(new BN(3_000), null)
+        .accountsPartial({
+          taker: outsider.publicKey,
+          maker: maker.publicKey,
+          mintX,
+          mintY,
+          escrow,
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          proceeds: null,
+        })
+        .signers([outsider])
+        .rpc();
+      assert.fail("take should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "UnauthorizedTaker");
+    }
+
+    await take(escrow, 3_000);
+
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  const setFee = (feeBps: number) =>
+    program.methods
+      .updateConfig(null, feeBps, null)
+      .accountsPartial({ admin: provider.publicKey })
+      .rpc();
+
+  it("Charges the taker one protocol fee per fill", async () => {
+    const seed = new BN(18);
+    const escrow = escrowPda(seed);
+    const treasuryAtaY = getAssociatedTokenAddressSync(
+      mintY,
+      treasury.publicKey
+    );
+    const makerYBefore = await balance(makerAtaY);
+    const takerXBefore = await balance(takerAtaX);
+
+    await setFee(50);
+
+    try {
+      await make(seed, 10_000, 20_000);
+
+      const takerYBefore = await balance(takerAtaY);
+      const events = await cpiEventsOf(await take(escrow, 20_000));
+
+      // 0.5% of the fill goes to the treasury, paid by the taker on top of
+      // the maker's full asking price. The taker receives the full payout.
+      assert.equal(await balance(makerAtaY), makerYBefore + 20_000);
+      assert.equal(await balance(takerAtaY), takerYBefore - 20_100);
+      assert.equal(await balance(takerAtaX), takerXBefore + 10_000);
+      assert.equal(await balance(treasuryAtaY), 100);
+
+      const fees = events.find((event) => event.name === "feesCollected");
+      assert.equal(fees.data.feeX.toNumber(), 0);
+      assert.equal(fees.data.feeY.toNumber(), 100);
+    } finally {
+      await setFee(0);
+    }
+  });
+
+  it("Only lets the admin update the config", async () => {
+    try {
+      await program.methods
+        .updateConfig(null, 100, null)
+        .accountsPartial({ admin: taker.publicKey })
+        .signers([taker])
+        .rpc();
+      assert.fail("update_config should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+  });
+
+  it("Caps the protocol fee", async () => {
+    try {
+      await setFee(1_001);
+      assert.fail("update_config should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidFee");
+    }
+  });
+
+  const update = (
+    escrow: anchor.web3.PublicKey,
+    receiveAmount: number,
+    depositChange: object | null = null
+  ) =>
+    program.methods
+      .update(new BN(receiveAmount), depositChange)
+      .accountsPartial({
+        maker: maker.publicKey,
+        mintX,
+        escrow,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([maker])
+      .rpc();
+
+  it("Reprices an open offer in place", async () => {
+    const seed = new BN(19);
+    const escrow = escrowPda(seed);
+
+    await make(seed, 1_000, 3_000);
+    await update(escrow, 2_000);
+
+    const escrowAccount = await program.account.escrow.fetch(escrow);
+    assert.equal(escrowAccount.seed.toNumber(), 19);
+    assert.equal(escrowAccount.receiveAmount.toNumber(), 2_000);
+    assert.equal(escrowAccount.remainingReceive.toNumber(), 2_000);
+
+    await take(escrow, 2_000);
+
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Tops up and withdraws part of the vault while repricing", async () => {
+    const seed = new BN(20);
+    const escrow = escrowPda(seed);
+    const vault = vaultFor(escrow);
+    const before = await balance(makerAtaX);
+
+    await make(seed, 1_000, 3_000);
+
+    await update(escrow, 4_000, { topUp: { amount: new BN(500) } });
+
+    let escrowAccount = await program.account.escrow.fetch(escrow);
+    assert.equal(escrowAccount.remainingDeposit.toNumber(), 1_500);
+    assert.equal(escrowAccount.remainingReceive.toNumber(), 4_000);
+    assert.equal(await balance(vault), 1_500);
+
+    await update(escrow, 1_000, { withdraw: { amount: new BN(1_200) } });
+
+    escrowAccount = await program.account.escrow.fetch(escrow);
+    assert.equal(escrowAccount.remainingDeposit.toNumber(), 300);
+    assert.equal(escrowAccount.remainingReceive.toNumber(), 1_000);
+    assert.equal(await balance(vault), 300);
+    assert.equal(await balance(makerAtaX), before - 300);
+  });
+
+  it("Only lets the maker update an offer", async () => {
+    const seed = new BN(21);
+
+    await make(seed, 1_000, 3_000);
+
+    try {
+      await program.methods
+        .update(new BN(1), null)
+        .accountsPartial({
+          maker: taker.publicKey,
+          mintX,
+          escrow: escrowPda(seed),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([taker])
+        .rpc();
+      assert.fail("update should have failed");
+    } catch (err) {
+      assert.instanceOf(err, anchor.AnchorError);
+    }
+  });
+
+  it("Keeps the minimum fill within the updated price", async () => {
+    const seed = new BN(39);
+    const escrow = escrowPda(seed);
+
+    await make(seed, 1_000, 3_000, { minFillAmount: 1_000 });
+
+    try {
+      await update(escrow, 500);
+      assert.fail("update should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidMinFillAmount");
+    }
+
+    await update(escrow, 1_000);
+
+    const escrowAccount = await program.account.escrow.fetch(escrow);
+    assert.equal(escrowAccount.remainingReceive.toNumber(), 1_000);
+  });
+
+  it("Sells native SOL without a vault", async () => {
+    const seed = new BN(22);
+    const escrow = escrowPda(seed);
+    const makerBefore = await connection.getBalance(maker.publicKey);
+    const deposit = anchor.web3.LAMPORTS_PER_SOL;
+
+    await program.methods
+      .make(seed, new BN(deposit), new BN(5_000), makeOptions({}))
+      .accountsPartial({
+        maker: maker.publicKey,
+        mintX: NATIVE_MINT,
+        mintY,
+        makerAtaX: null,
+        vault: null,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ...noReceipt,
+      })
+      .signers([maker])
+      .rpc();
+
+    const rent = await connection.getMinimumBalanceForRentExemption(
+      program.account.escrow.size
+    );
+    assert.equal(await connection.getBalance(escrow), rent + deposit);
+    assert.equal(
+      await connection.getBalance(maker.publicKey),
+      makerBefore - rent - deposit
+    );
+
+    const takerBefore = await connection.getBalance(taker.publicKey);
+    const makerAtaYBefore = await balance(makerAtaY);
+
+    await program.methods
+      .take(new BN(5_000), null)
+      .accountsPartial({
+        taker: taker.publicKey,
+        maker: maker.publicKey,
+        mintX: NATIVE_MINT,
+        mintY,
+        takerAtaX: null,
+        vault: null,
+        escrow,
+        treasury: treasury.publicKey,
+        treasuryAtaY: null,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        proceeds: null,
+      })
+      .signers([taker])
+      .rpc();
+
+    assert.equal(
+      await connection.getBalance(taker.publicKey),
+      takerBefore + deposit
+    );
+    assert.equal(await balance(makerAtaY), makerAtaYBefore + 5_000);
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Buys native SOL straight into the maker's wallet", async () => {
+    const seed = new BN(23);
+    const escrow = escrowPda(seed);
+    const vault = vaultFor(escrow);
+    const ask = anchor.web3.LAMPORTS_PER_SOL / 2;
+
+    await program.methods
+      .make(seed, new BN(1_000), new BN(ask), makeOptions({}))
+      .accountsPartial({
+        maker: maker.publicKey,
+        mintX,
+        mintY: NATIVE_MINT,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ...noReceipt,
+      })
+      .signers([maker])
+      .rpc();
+
+    const makerBefore = await connection.getBalance(maker.publicKey);
+    const rent =
+      (await connection.getBalance(escrow)) +
+      (await connection.getBalance(vault));
+    const takerAtaXBefore = await balance(takerAtaX);
+
+    await program.methods
+      .take(new BN(ask), null)
+      .accountsPartial({
+        taker: taker.publicKey,
+        maker: maker.publicKey,
+        mintX,
+        mintY: NATIVE_MINT,
+        takerAtaY: null,
+        makerAtaY: null,
+        escrow,
+        treasury: treasury.publicKey,
+        treasuryAtaY: null,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        proceeds: null,
+      })
+      .signers([taker])
+      .rpc();
+
+    assert.equal(
+      await connection.getBalance(maker.publicKey),
+      makerBefore + ask + rent
+    );
+    assert.equal(await balance(takerAtaX), takerAtaXBefore + 1_000);
+    assert.isNull(await connection.getAccountInfo(vault));
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Refunds a native SOL offer when the escrow closes", async () => {
+    const seed = new BN(24);
+    const escrow = escrowPda(seed);
+    const makerBefore = await connection.getBalance(maker.publicKey);
+
+    await program.methods
+      .make(seed, new BN(2_000_000), new BN(5_000), makeOptions({}))
+      .accountsPartial({
+        maker: maker.publicKey,
+        mintX: NATIVE_MINT,
+        mintY,
+        makerAtaX: null,
+        vault: null,
+        tokenProgramX: TOKEN_PROGRAM_ID,
+        tokenProgramY: TOKEN_PROGRAM_ID,
+        ...noReceipt,
+      })
+      .signers([maker])
+      .rpc();
+
+    await program.methods
+      .refund()
+      .accountsPartial({
+        maker: maker.publicKey,
+        mintX: NATIVE_MINT,
+        makerAtaX: null,
+        escrow,
+        vault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([maker])
+      .rpc();
+
+    assert.equal(await connection.getBalance(maker.publicKey), makerBefore);
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Rejects token accounts on a native SOL leg", async () => {
+    try {
+      await program.methods
+        .make(new BN(25), new BN(1_000), new BN(5_000), makeOptions({}))
+        .accountsPartial({
+          maker: maker.publicKey,
+          mintX: NATIVE_MINT,
+          mintY,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          ...noReceipt,
+        })
+        .signers([maker])
+        .rpc();
+      assert.fail("make should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidTokenAccounts");
+    }
+  });
+
+  it("Keeps the market's list of open offers in sync", async () => {
+    const seed = new BN(26);
+    const escrow = escrowPda(seed);
+
+    const listing = async () => {
+      const market = await program.account.market.fetch(
+        marketPda(mintX, mintY)
+      );
+      return market.entries
+        .slice(0, market.len)
+        .find((entry) => entry.escrow.equals(escrow));
+    };
+
+    await make(seed, 1_000, 4_000);
+
+    let entry = await listing();
+    assert.equal(entry.remainingDeposit.toNumber(), 1_000);
+    assert.equal(entry.remainingReceive.toNumber(), 4_000);
+
+    await take(escrow, 1_002);
+
+    entry = await listing();
+    assert.equal(entry.remainingDeposit.toNumber(), 750);
+    assert.equal(entry.remainingReceive.toNumber(), 2_998);
+
+    await refund(escrow);
+
+    assert.isUndefined(await listing());
+  });
+
+  it("Lists offers left out of a full market once there is room", async () => {
+    // A pair of its own, so the filler offers don't crowd other tests.
+    const mintFull = await createMint(
+      connection,
+      payer,
+      payer.publicKey,
+      null,
+      6
+    );
+    await createMarket(mintX, mintFull);
+
+    const makeFor = (seed: BN) =>
+      program.methods
+        .make(seed, new BN(1), new BN(1), makeOptions({}))
+        .accountsPartial({
+          maker: maker.publicKey,
+          mintX,
+          mintY: mintFull,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          ...noReceipt,
+        })
+        .signers([maker])
+        .rpc();
+
+    // MAX_MARKET_ENTRIES
+    for (let i = 0; i < 128; i++) {
+      await makeFor(new BN(1_000 + i));
+    }
+
+    const seed = new BN(1_128);
+    const escrow = escrowPda(seed);
+
+    await makeFor(seed);
+
+    const refundFor = (escrow: anchor.web3.PublicKey) =>
+      program.methods
+        .refund()
+        .accountsPartial({
+          maker: maker.publicKey,
+          mintX,
+          escrow,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+    const listed = async () => {
+      const market = await program.account.market.fetch(
+        marketPda(mintX, mintFull)
+      );
+      return market.entries
+        .slice(0, market.len)
+        .some((entry) => entry.escrow.equals(escrow));
+    };
+
+    assert.isFalse(await listed());
+    assert.isFalse((await program.account.escrow.fetch(escrow)).isListed);
+
+    // Nothing can be listed until an entry frees up.
+    try {
+      await program.methods.list().accountsPartial({ escrow }).rpc();
+      assert.fail("list should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "MarketFull");
+    }
+
+    await refundFor(escrowPda(new BN(1_000)));
+    await program.methods.list().accountsPartial({ escrow }).rpc();
+
+    assert.isTrue(await listed());
+    assert.isTrue((await program.account.escrow.fetch(escrow)).isListed);
+
+    // The offer is delisted like any other once it closes.
+    await refundFor(escrow);
+
+    assert.isFalse(await listed());
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  it("Emits offer events through event CPI", async () => {
+    const seed = new BN(27);
+    const escrow = escrowPda(seed);
+
+    const [made] = await cpiEventsOf(await make(seed, 1_000, 2_000));
+    assert.equal(made.name, "offerMade");
+    assert.isTrue(made.data.escrow.equals(escrow));
+    assert.isTrue(made.data.maker.equals(maker.publicKey));
+    assert.isNull(made.data.taker);
+    assert.isTrue(made.data.mintX.equals(mintX));
+    assert.isTrue(made.data.mintY.equals(mintY));
+    assert.equal(made.data.depositAmount.toNumber(), 1_000);
+    assert.equal(made.data.receiveAmount.toNumber(), 2_000);
+
+    // Fees are reported just before the fill.
+    const [fees, taken] = await cpiEventsOf(await take(escrow, 1_003));
+    assert.equal(fees.name, "feesCollected");
+    assert.equal(taken.name, "offerTaken");
+    assert.isTrue(taken.data.taker.equals(taker.publicKey));
+    assert.equal(taken.data.fillAmount.toNumber(), 1_003);
+    assert.equal(taken.data.payout.toNumber(), 501);
+    assert.equal(taken.data.remainingDeposit.toNumber(), 499);
+    assert.equal(taken.data.remainingReceive.toNumber(), 997);
+
+    const [updated] = await cpiEventsOf(await update(escrow, 1_200));
+    assert.equal(updated.name, "offerUpdated");
+    assert.isTrue(updated.data.escrow.equals(escrow));
+    assert.equal(updated.data.remainingDeposit.toNumber(), 499);
+    assert.equal(updated.data.remainingReceive.toNumber(), 1_200);
+
+    const [refunded] = await cpiEventsOf(await refund(escrow));
+    assert.equal(refunded.name, "offerRefunded");
+    assert.isTrue(refunded.data.escrow.equals(escrow));
+    assert.equal(refunded.data.refundAmount.toNumber(), 499);
+  });
+
+  it("Gates a hash-timelocked offer on its secret and timeout", async () => {
+    const seed = new BN(32);
+    const escrow = escrowPda(seed);
+    const before = await balance(makerAtaX);
+
+    const outsider = anchor.web3.Keypair.generate();
+    for (const mint of [mintX, mintY]) {
+      await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mint,
+        outsider.publicKey
+      );
+    }
+
+    const expiresAt = (await chainTime()) + 10;
+
+    const secret = randomBytes(32);
+    const hashlock = [...createHash("sha256").update(secret).digest()];
+
+    try {
+      await make(seed, 1_000, 3_000, { expiresAt, hashlock });
+      assert.fail("make should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "HashlockWithoutTaker");
+    }
+
+    await make(seed, 1_000, 3_000, {
+      expiresAt,
+      hashlock,
+      taker: taker.publicKey,
+    });
+
+    try {
+      await refund(escrow);
+      assert.fail("refund should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "HashlockActive");
+    }
+
+    for (const preimage of [null, [...randomBytes(32)]]) {
+      try {
+        await take(escrow, 1_500, preimage);
+        assert.fail("take should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "InvalidPreimage");
+      }
+    }
+
+    await take(escrow, 1_500, [...secret]);
+
+    // The secret is public once revealed, but only the named taker can use
+    // it.
+    try {
+      await program.methods
+        .take(new BN(1_500), [...secret])
+        .accountsPartial({
+          taker: outsider.publicKey,
+          maker: maker.publicKey,
+          mintX,
+          mintY,
+          escrow,
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          proceeds: null,
+        })
+        .signers([outsider])
+        .rpc();
+      assert.fail("take should have failed");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "UnauthorizedTaker");
+    }
+
+    while ((await chainTime()) < expiresAt) {
+      await sleep(500);
+    }
+
+    // Past the timeout the maker can reclaim whatever was not taken.
+    await refund(escrow);
+
+    assert.equal(await balance(makerAtaX), before - 500);
+    assert.isNull(await connection.getAccountInfo(escrow));
+  });
+
+  describe("signed orders", () => {
+    const [delegate] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("delegate")],
+      program.programId
+    );
+
+    const signedOrder = (nonce: number, expiresAt: number) => ({
+      maker: maker.publicKey,
+      mintX,
+      mintY,
+      depositAmount: new BN(1_000),
+      receiveAmount: new BN(3_000),
+      nonce: new BN(nonce),
+      expiresAt: new BN(expiresAt),
+    });
+
+    const filledOrderPda = (nonce: BN) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("order"),
+          maker.publicKey.toBuffer(),
+          nonce.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    // What the maker signs: the order, bound to this program.
+    const orderMessage = (order: ReturnType<typeof signedOrder>) =>
+      Buffer.concat([
+        Buffer.from("escrow:signed-order"),
+        program.programId.toBuffer(),
+        program.coder.types.encode("signedOrder", order),
+      ]);
+
+    // The taker submits the maker's signature through the Ed25519 program
+    // instruction directly before the fill.
+    const fillSignedOrder = (
+      order: ReturnType<typeof signedOrder>,
+      signer = maker
+    ) =>
+      program.methods
+        .fillSignedOrder(order)
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker: maker.publicKey,
+          mintX,
+          mintY,
+          filledOrder: filledOrderPda(order.nonce),
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .preInstructions([
+          anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+            privateKey: signer.secretKey,
+            message: orderMessage(order),
+          }),
+        ])
+        .signers([taker])
+        .rpc();
+
+    before(async () => {
+      await approve(connection, payer, makerAtaX, delegate, maker, 10_000);
+    });
+
+    it("Fills a signed order through the maker's delegate", async () => {
+      const order = signedOrder(1, (await chainTime()) + 600);
+
+      const makerXBefore = await balance(makerAtaX);
+      const makerYBefore = await balance(makerAtaY);
+      const takerXBefore = await balance(takerAtaX);
+
+      await fillSignedOrder(order);
+
+      assert.equal(await balance(makerAtaX), makerXBefore - 1_000);
+      assert.equal(await balance(makerAtaY), makerYBefore + 3_000);
+      assert.equal(await balance(takerAtaX), takerXBefore + 1_000);
+
+      try {
+        await fillSignedOrder(order);
+        assert.fail("replaying the order should have failed");
+      } catch (err) {
+        assert.isTrue(
+          err.logs.some((log: string) => log.includes("already in use"))
+        );
+      }
+    });
+
+    it("Rejects an order not signed by its maker", async () => {
+      const order = signedOrder(2, (await chainTime()) + 600);
+
+      try {
+        await fillSignedOrder(order, taker);
+        assert.fail("fill should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "InvalidSignature");
+      }
+    });
+
+    it("Rejects an expired signed order", async () => {
+      const order = signedOrder(3, (await chainTime()) - 1);
+
+      try {
+        await fillSignedOrder(order);
+        assert.fail("fill should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "OfferExpired");
+      }
+    });
+
+    it("Lets the maker cancel a single signed order", async () => {
+      const order = signedOrder(4, (await chainTime()) + 600);
+
+      await program.methods
+        .cancelSignedOrder(order)
+        .accountsPartial({
+          maker: maker.publicKey,
+          filledOrder: filledOrderPda(order.nonce),
+        })
+        .signers([maker])
+        .rpc();
+
+      try {
+        await fillSignedOrder(order);
+        assert.fail("fill should have failed");
+      } catch (err) {
+        assert.isTrue(
+          err.logs.some((log: string) => log.includes("already in use"))
+        );
+      }
+
+      // Other orders under the same approval still fill.
+      await fillSignedOrder(signedOrder(5, (await chainTime()) + 600));
+    });
+
+    it("Returns a spent nonce's rent once the order expires", async () => {
+      const order = signedOrder(6, (await chainTime()) + 3);
+      const filledOrder = filledOrderPda(order.nonce);
+
+      await fillSignedOrder(order);
+
+      const closeSignedOrder = () =>
+        program.methods
+          .closeSignedOrder()
+          .accountsPartial({ payer: taker.publicKey, filledOrder })
+          .rpc();
+
+      try {
+        await closeSignedOrder();
+        assert.fail("close should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "OfferNotExpired");
+      }
+
+      while ((await chainTime()) < order.expiresAt.toNumber()) {
+        await sleep(500);
+      }
+
+      const rent = await connection.getBalance(filledOrder);
+      const takerBefore = await connection.getBalance(taker.publicKey);
+
+      await closeSignedOrder();
+
+      assert.isNull(await connection.getAccountInfo(filledOrder));
+      assert.equal(
+        await connection.getBalance(taker.publicKey),
+        takerBefore + rent
+      );
+    });
+  });
+
+  describe("arbitration", () => {
+    const arbiter = anchor.web3.Keypair.generate();
+
+    const arbitrationPda = (seed: BN) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("arbitration"),
+          maker.publicKey.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    // The maker pays the taker, with the arbiter settling disputes.
+    const makeArbitrated = (seed: BN, amount: number) =>
+      program.methods
+        .makeArbitrated(
+          seed,
+          new BN(amount),
+          taker.publicKey,
+          arbiter.publicKey
+        )
+        .accountsPartial({
+          payer: maker.publicKey,
+          mint: mintX,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+    const settleAccounts = (
+      authority: anchor.web3.Keypair,
+      arbitration: anchor.web3.PublicKey
+    ) => ({
+      authority: authority.publicKey,
+      payer: maker.publicKey,
+      payee: taker.publicKey,
+      mint: mintX,
+      arbitration,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    before(async () => {
+      const sig = await connection.requestAirdrop(
+        arbiter.publicKey,
+        anchor.web3.LAMPORTS_PER_SOL
+      );
+      await connection.confirmTransaction(sig);
+    });
+
+    it("Lets the payer release the payment to the payee", async () => {
+      const seed = new BN(1);
+      const arbitration = arbitrationPda(seed);
+      const takerBefore = await balance(takerAtaX);
+
+      await makeArbitrated(seed, 1_000);
+
+      try {
+        await program.methods
+          .release()
+          .accountsPartial(settleAccounts(taker, arbitration))
+          .signers([taker])
+          .rpc();
+        assert.fail("release should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "Unauthorized");
+      }
+
+      await program.methods
+        .release()
+        .accountsPartial(settleAccounts(maker, arbitration))
+        .signers([maker])
+        .rpc();
+
+      assert.equal(await balance(takerAtaX), takerBefore + 1_000);
+      assert.isNull(await connection.getAccountInfo(arbitration));
+    });
+
+    it("Lets the payee refund the payer", async () => {
+      const seed = new BN(2);
+      const arbitration = arbitrationPda(seed);
+      const makerBefore = await balance(makerAtaX);
+
+      await makeArbitrated(seed, 1_000);
+      await program.methods
+        .refundArbitrated()
+        .accountsPartial(settleAccounts(taker, arbitration))
+        .signers([taker])
+        .rpc();
+
+      assert.equal(await balance(makerAtaX), makerBefore);
+      assert.isNull(await connection.getAccountInfo(arbitration));
+    });
+
+    it("Lets the arbiter split a disputed payment", async () => {
+      const seed = new BN(3);
+      const arbitration = arbitrationPda(seed);
+      const makerBefore = await balance(makerAtaX);
+      const takerBefore = await balance(takerAtaX);
+
+      await makeArbitrated(seed, 1_000);
+
+      const arbitrate = () =>
+        program.methods
+          .arbitrate(70)
+          .accountsPartial(settleAccounts(arbiter, arbitration))
+          .signers([arbiter])
+          .rpc();
+
+      try {
+        await arbitrate();
+        assert.fail("arbitrate should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "NotDisputed");
+      }
+
+      await program.methods
+        .dispute()
+        .accountsPartial({ authority: taker.publicKey, arbitration })
+        .signers([taker])
+        .rpc();
+      await arbitrate();
+
+      assert.equal(await balance(takerAtaX), takerBefore + 700);
+      assert.equal(await balance(makerAtaX), makerBefore - 700);
+      assert.isNull(await connection.getAccountInfo(arbitration));
+    });
+  });
+
+  describe("milestones", () => {
+    const milestonePda = (seed: BN) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("milestones"),
+          maker.publicKey.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    // The maker pays the taker in stages.
+    const makeMilestones = (
+      seed: BN,
+      milestones: { amount: number; unlockAt?: number }[]
+    ) =>
+      program.methods
+        .makeMilestones(
+          seed,
+          taker.publicKey,
+          milestones.map(({ amount, unlockAt }) => ({
+            amount: new BN(amount),
+            unlockAt: unlockAt === undefined ? null : new BN(unlockAt),
+          }))
+        )
+        .accountsPartial({
+          payer: maker.publicKey,
+          mint: mintX,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+    const releaseMilestone = (
+      milestoneEscrow: anchor.web3.PublicKey,
+      index: number
+    ) =>
+      program.methods
+        .releaseMilestone(index)
+        .accountsPartial({
+          payer: maker.publicKey,
+          payee: taker.publicKey,
+          mint: mintX,
+          milestoneEscrow,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+    const cancelMilestones = (milestoneEscrow: anchor.web3.PublicKey) =>
+      program.methods
+        .cancelMilestones()
+        .accountsPartial({
+          payer: maker.publicKey,
+          mint: mintX,
+          milestoneEscrow,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+    it("Refunds only unreleased milestones on cancel", async () => {
+      const seed = new BN(1);
+      const milestoneEscrow = milestonePda(seed);
+      const makerBefore = await balance(makerAtaX);
+      const takerBefore = await balance(takerAtaX);
+
+      await makeMilestones(seed, [
+        { amount: 100 },
+        { amount: 200 },
+        { amount: 300 },
+      ]);
+
+      await releaseMilestone(milestoneEscrow, 1);
+
+      try {
+        await releaseMilestone(milestoneEscrow, 1);
+        assert.fail("release should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "MilestoneReleased");
+      }
+
+      await cancelMilestones(milestoneEscrow);
+
+      assert.equal(await balance(takerAtaX), takerBefore + 200);
+      assert.equal(await balance(makerAtaX), makerBefore - 200);
+      assert.isNull(await connection.getAccountInfo(milestoneEscrow));
+    });
+
+    it("Holds milestones until they unlock", async () => {
+      const seed = new BN(2);
+      const milestoneEscrow = milestonePda(seed);
+
+      await makeMilestones(seed, [
+        { amount: 100, unlockAt: (await chainTime()) + 600 },
+      ]);
+
+      try {
+        await releaseMilestone(milestoneEscrow, 0);
+        assert.fail("release should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "MilestoneLocked");
+      }
+
+      await cancelMilestones(milestoneEscrow);
+    });
+  });
+
+  describe("matching", () => {
+    // The taker posts offers for the other side of the pair as a maker.
+    const counterEscrowPda = (seed: BN) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("escrow"),
+          taker.publicKey.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    const makeCounterOffer = (
+      seed: BN,
+      depositAmount: number,
+      receiveAmount: number
+    ) =>
+      program.methods
+        .make(
+          seed,
+          new BN(depositAmount),
+          new BN(receiveAmount),
+          makeOptions({})
+        )
+        .accountsPartial({
+          maker: taker.publicKey,
+          mintX: mintY,
+          mintY: mintX,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          ...noReceipt,
+        })
+        .signers([taker])
+        .rpc();
+
+    const matchOffers = (
+      escrowA: anchor.web3.PublicKey,
+      escrowB: anchor.web3.PublicKey
+    ) =>
+      program.methods
+        .matchOffers()
+        .accountsPartial({
+          caller: provider.publicKey,
+          makerA: maker.publicKey,
+          makerB: taker.publicKey,
+          mintX,
+          mintY,
+          escrowA,
+          escrowB,
+          marketA: marketPda(mintX, mintY),
+          marketB: marketPda(mintY, mintX),
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    before(async () => {
+      await createMarket(mintY, mintX);
+    });
+
+    it("Settles crossing offers and leaves the remainder open", async () => {
+      const escrowA = escrowPda(new BN(33));
+      const escrowB = counterEscrowPda(new BN(1));
+
+      const makerYBefore = await balance(makerAtaY);
+      const takerXBefore = await balance(takerAtaX);
+
+      // Asks 2 Y per X against a bid of 3 Y per X.
+      await make(new BN(33), 1_000, 2_000);
+      await makeCounterOffer(new BN(1), 4_500, 1_500);
+
+      await matchOffers(escrowA, escrowB);
+
+      // The first offer is filled in full and each maker gets their ask. The
+      // second offer pays at its own price, so the spread goes to the
+      // treasury.
+      assert.equal(await balance(makerAtaY), makerYBefore + 2_000);
+      assert.equal(await balance(takerAtaX), takerXBefore + 1_000);
+      assert.isNull(await connection.getAccountInfo(escrowA));
+
+      const escrowAccount = await program.account.escrow.fetch(escrowB);
+      assert.equal(escrowAccount.remainingDeposit.toNumber(), 1_500);
+      assert.equal(escrowAccount.remainingReceive.toNumber(), 500);
+    });
+
+    it("Pays the protocol fee on both fills out of the spread", async () => {
+      const escrowA = escrowPda(new BN(43));
+      const escrowB = counterEscrowPda(new BN(3));
+      const treasuryAtaX = getAssociatedTokenAddressSync(
+        mintX,
+        treasury.publicKey
+      );
+      const treasuryAtaY = getAssociatedTokenAddressSync(
+        mintY,
+        treasury.publicKey
+      );
+
+      await make(new BN(43), 1_000, 2_000);
+      await makeCounterOffer(new BN(3), 4_500, 1_500);
+
+      const makerYBefore = await balance(makerAtaY);
+      const takerXBefore = await balance(takerAtaX);
+      const treasuryXBefore = await balance(treasuryAtaX);
+      const treasuryYBefore = await balance(treasuryAtaY);
+
+      await setFee(50);
+      try {
+        await matchOffers(escrowA, escrowB);
+      } finally {
+        await setFee(0);
+      }
+
+      // The first maker gets their full ask. The second gets as much of the
+      // first deposit as fits with its 0.5% fee on top.
+      assert.equal(await balance(makerAtaY), makerYBefore + 2_000);
+      assert.equal(await balance(takerAtaX), takerXBefore + 996);
+      assert.equal(await balance(treasuryAtaX), treasuryXBefore + 4);
+      // 2_988 paid for the 996 at the second offer's price, less the ask.
+      assert.equal(await balance(treasuryAtaY), treasuryYBefore + 988);
+      assert.isNull(await connection.getAccountInfo(escrowA));
+
+      const escrowAccount = await program.account.escrow.fetch(escrowB);
+      assert.equal(escrowAccount.remainingDeposit.toNumber(), 1_512);
+      assert.equal(escrowAccount.remainingReceive.toNumber(), 504);
+    });
+
+    it("Rejects offers whose prices do not cross", async () => {
+      // Asks 2 Y per X against a bid of 1 Y per X.
+      await make(new BN(34), 1_000, 2_000);
+      await makeCounterOffer(new BN(2), 1_000, 1_000);
+
+      try {
+        await matchOffers(escrowPda(new BN(34)), counterEscrowPda(new BN(2)));
+        assert.fail("match should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "OffersDoNotCross");
+      }
+    });
+
+    it("Rejects matching an offer against itself", async () => {
+      const escrow = escrowPda(new BN(40));
+      await make(new BN(40), 1_000, 2_000);
+
+      try {
+        await program.methods
+          .matchOffers()
+          .accountsPartial({
+            caller: provider.publicKey,
+            makerA: maker.publicKey,
+            makerB: maker.publicKey,
+            mintX,
+            mintY,
+            escrowA: escrow,
+            escrowB: escrow,
+            marketA: marketPda(mintX, mintY),
+            marketB: marketPda(mintX, mintY),
+            vaultB: null,
+            treasury: treasury.publicKey,
+            tokenProgramX: TOKEN_PROGRAM_ID,
+            tokenProgramY: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+        assert.fail("match should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "OffersDoNotCross");
+      }
+    });
+  });
+
+  describe("receipts", () => {
+    const holder = anchor.web3.Keypair.generate();
+
+    before(async () => {
+      const sig = await connection.requestAirdrop(
+        holder.publicKey,
+        anchor.web3.LAMPORTS_PER_SOL
+      );
+      await connection.confirmTransaction(sig);
+    });
+
+    it("Settles an offer for whoever holds its receipt", async () => {
+      const seed = new BN(35);
+      const escrow = escrowPda(seed);
+      const [receiptMint] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("receipt"), escrow.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .make(
+          seed,
+          new BN(1_000),
+          new BN(2_000),
+          makeOptions({ receipt: true })
+        )
+        .accountsPartial({
+          maker: maker.publicKey,
+          mintX,
+          mintY,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+      // Hand the position over to someone else.
+      const holderReceipt = (
+        await getOrCreateAssociatedTokenAccount(
+          connection,
+          payer,
+          receiptMint,
+          holder.publicKey
+        )
+      ).address;
+      await transfer(
+        connection,
+        payer,
+        getAssociatedTokenAddressSync(receiptMint, maker.publicKey),
+        holderReceipt,
+        maker,
+        1
+      );
+
+      try {
+        await refund(escrow);
+        assert.fail("refund should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "ReceiptRequired");
+      }
+
+      const makerYBefore = await balance(makerAtaY);
+
+      await program.methods
+        .take(new BN(1_000), null)
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker: maker.publicKey,
+          mintX,
+          mintY,
+          escrow,
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .signers([taker])
+        .rpc();
+
+      // Proceeds wait in the escrow rather than going to the maker.
+      assert.equal(await balance(makerAtaY), makerYBefore);
+
+      await program.methods
+        .redeem()
+        .accountsPartial({
+          holder: holder.publicKey,
+          mintX,
+          mintY,
+          holderReceipt,
+          escrow,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .signers([holder])
+        .rpc();
+
+      const holderAta = (mint: anchor.web3.PublicKey) =>
+        getAssociatedTokenAddressSync(mint, holder.publicKey);
+
+      assert.equal(await balance(holderAta(mintX)), 500);
+      assert.equal(await balance(holderAta(mintY)), 1_000);
+      assert.equal(await balance(holderReceipt), 0);
+      assert.isNull(await connection.getAccountInfo(escrow));
+    });
+  });
+
+  describe("sweeping", () => {
+    // Each offer is passed as its escrow, vault, maker and maker's Y account.
+    const offer = (seed: BN) =>
+      [
+        escrowPda(seed),
+        getAssociatedTokenAddressSync(mintX, escrowPda(seed), true),
+        maker.publicKey,
+        makerAtaY,
+      ].map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+
+    const takeMany = (seeds: BN[], maxPay: number) =>
+      program.methods
+        .takeMany(new BN(maxPay))
+        .accountsPartial({
+          taker: taker.publicKey,
+          mintX,
+          mintY,
+          market: marketPda(mintX, mintY),
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(seeds.flatMap(offer))
+        .signers([taker])
+        .rpc();
+
+    it("Fills the requested amount across offers in order", async () => {
+      const makerYBefore = await balance(makerAtaY);
+      const takerXBefore = await balance(takerAtaX);
+
+      await make(new BN(36), 1_000, 2_000);
+      await make(new BN(37), 1_000, 3_000);
+
+      await takeMany([new BN(36), new BN(37)], 3_500);
+
+      assert.equal(await balance(makerAtaY), makerYBefore + 3_500);
+      assert.equal(await balance(takerAtaX), takerXBefore + 1_500);
+      assert.isNull(await connection.getAccountInfo(escrowPda(new BN(36))));
+
+      const escrowAccount = await program.account.escrow.fetch(
+        escrowPda(new BN(37))
+      );
+      assert.equal(escrowAccount.remainingDeposit.toNumber(), 500);
+      assert.equal(escrowAccount.remainingReceive.toNumber(), 1_500);
+    });
+
+    it("Fails when the offers cannot fill the requested amount", async () => {
+      await make(new BN(38), 1_000, 2_000);
+
+      try {
+        await takeMany([new BN(38)], 4_000);
+        assert.fail("take should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "FillNotAchievable");
+      }
+
+      // Nothing was filled.
+      const escrowAccount = await program.account.escrow.fetch(
+        escrowPda(new BN(38))
+      );
+      assert.equal(escrowAccount.remainingDeposit.toNumber(), 1_000);
+    });
+
+    it("Skips offers that cannot be taken", async () => {
+      const makerYBefore = await balance(makerAtaY);
+      const takerXBefore = await balance(takerAtaX);
+
+      await make(new BN(41), 1_000, 2_000, {
+        taker: anchor.web3.Keypair.generate().publicKey,
+      });
+      await make(new BN(42), 1_000, 2_000);
+
+      // The first offer is private and the second was filled above.
+      await takeMany([new BN(41), new BN(36), new BN(42)], 2_000);
+
+      assert.equal(await balance(makerAtaY), makerYBefore + 2_000);
+      assert.equal(await balance(takerAtaX), takerXBefore + 1_000);
+      assert.isNull(await connection.getAccountInfo(escrowPda(new BN(42))));
+
+      const escrowAccount = await program.account.escrow.fetch(
+        escrowPda(new BN(41))
+      );
+      assert.equal(escrowAccount.remainingDeposit.toNumber(), 1_000);
+    });
+  });
+
+  describe("nfts", () => {
+    const collection = anchor.web3.Keypair.generate().publicKey;
+
+    const metadataPda = (mint: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("metadata"), mint.toBuffer()],
+        program.programId
+      )[0];
+
+    // Mints a single token to `owner`, records its collection and then
+    // revokes the mint authority so the supply stays at one.
+    const createNft = async (
+      owner: anchor.web3.PublicKey,
+      nftCollection?: anchor.web3.PublicKey
+    ) => {
+      const mint = await createMint(
+        connection,
+        payer,
+        payer.publicKey,
+        null,
+        0
+      );
+      const ata = (
+        await getOrCreateAssociatedTokenAccount(connection, payer, mint, owner)
+      ).address;
+      await mintTo(connection, payer, mint, ata, payer, 1);
+
+      if (nftCollection) {
+        await program.methods
+          .createNftMetadata(nftCollection)
+          .accountsPartial({ authority: payer.publicKey, mint })
+          .rpc();
+      }
+
+      await setAuthority(
+        connection,
+        payer,
+        mint,
+        payer,
+        AuthorityType.MintTokens,
+        null
+      );
+      return { mint, ata };
+    };
+
+    const makeNft = (
+      seed: BN,
+      x: anchor.web3.PublicKey,
+      y: anchor.web3.PublicKey,
+      depositAmount: number,
+      options: MakeOptions
+    ) =>
+      program.methods
+        .make(seed, new BN(depositAmount), new BN(1), makeOptions(options))
+        .accountsPartial({
+          maker: maker.publicKey,
+          mintX: x,
+          mintY: y,
+          metadataX: options.nftX?.collection ? metadataPda(x) : null,
+          metadataY: null,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          ...noReceipt,
+        })
+        .signers([maker])
+        .rpc();
+
+    it("Swaps an NFT from a collection for another NFT", async () => {
+      const nftX = await createNft(maker.publicKey, collection);
+      const nftY = await createNft(taker.publicKey);
+      await createMarket(nftX.mint, nftY.mint);
+
+      const seed = new BN(28);
+      const escrow = escrowPda(seed);
+
+      await makeNft(seed, nftX.mint, nftY.mint, 1, {
+        nftX: { collection },
+        nftY: { collection: null },
+      });
+
+      await program.methods
+        .take(new BN(1), null)
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker: maker.publicKey,
+          mintX: nftX.mint,
+          mintY: nftY.mint,
+          escrow,
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          proceeds: null,
+        })
+        .signers([taker])
+        .rpc();
+
+      const takerNft = getAssociatedTokenAddressSync(
+        nftX.mint,
+        taker.publicKey
+      );
+      const makerNft = getAssociatedTokenAddressSync(
+        nftY.mint,
+        maker.publicKey
+      );
+
+      assert.equal(await balance(takerNft), 1);
+      assert.equal(await balance(makerNft), 1);
+      assert.equal(await balance(nftX.ata), 0);
+      assert.equal(await balance(nftY.ata), 0);
+      assert.isNull(await connection.getAccountInfo(escrow));
+    });
+
+    it("Rejects an NFT from a different collection", async () => {
+      const nftX = await createNft(maker.publicKey, collection);
+      const nftY = await createNft(taker.publicKey);
+      await createMarket(nftX.mint, nftY.mint);
+
+      try {
+        await makeNft(new BN(29), nftX.mint, nftY.mint, 1, {
+          nftX: { collection: anchor.web3.Keypair.generate().publicKey },
+        });
+        assert.fail("make should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "CollectionMismatch");
+      }
+    });
+
+    it("Rejects NFT amounts other than one", async () => {
+      const nftX = await createNft(maker.publicKey);
+      const nftY = await createNft(taker.publicKey);
+      await createMarket(nftX.mint, nftY.mint);
+
+      try {
+        await makeNft(new BN(30), nftX.mint, nftY.mint, 2, {
+          nftX: { collection: null },
+        });
+        assert.fail("make should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "InvalidNftAmount");
+      }
+    });
+
+    it("Rejects fungible mints flagged as NFTs", async () => {
+      try {
+        await makeNft(new BN(31), mintX, mintY, 1, {
+          nftX: { collection: null },
+        });
+        assert.fail("make should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "InvalidNft");
+      }
+    });
+  });
+
+  describe("baskets", () => {
+    const basketPda = (seed: BN) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("basket"),
+          maker.publicKey.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    const vaultOf = (seed: BN, mint: anchor.web3.PublicKey) =>
+      getAssociatedTokenAddressSync(mint, basketPda(seed), true);
+
+    const writable = (pubkey: anchor.web3.PublicKey) => ({
+      pubkey,
+      isSigner: false,
+      isWritable: true,
+    });
+    const readonly = (pubkey: anchor.web3.PublicKey) => ({
+      pubkey,
+      isSigner: false,
+      isWritable: false,
+    });
+
+    const leg = (mint: anchor.web3.PublicKey, amount: number) => ({
+      mint,
+      amount: new BN(amount),
+    });
+
+    let mintZ: anchor.web3.PublicKey;
+    let mintW: anchor.web3.PublicKey;
+    let makerAtaZ: anchor.web3.PublicKey;
+    let makerAtaW: anchor.web3.PublicKey;
+    let takerAtaZ: anchor.web3.PublicKey;
+    let takerAtaW: anchor.web3.PublicKey;
+
+    const ata = async (
+      mint: anchor.web3.PublicKey,
+      owner: anchor.web3.PublicKey
+    ) =>
+      (await getOrCreateAssociatedTokenAccount(connection, payer, mint, owner))
+        .address;
+
+    before(async () => {
+      mintZ = await createMint(connection, payer, payer.publicKey, null, 6);
+      mintW = await createMint(connection, payer, payer.publicKey, null, 6);
+
+      makerAtaZ = await ata(mintZ, maker.publicKey);
+      makerAtaW = await ata(mintW, maker.publicKey);
+      takerAtaZ = await ata(mintZ, taker.publicKey);
+      takerAtaW = await ata(mintW, taker.publicKey);
+
+      await mintTo(connection, payer, mintZ, makerAtaZ, payer, 1_000_000);
+      await mintTo(connection, payer, mintW, takerAtaW, payer, 1_000_000);
+    });
+
+    const makeBasket = (seed: BN) =>
+      program.methods
+        .makeBasket(
+          seed,
+          [leg(mintX, 700), leg(mintZ, 1_000)],
+          [leg(mintY, 2_000), leg(mintW, 3_000)],
+          null,
+          null
+        )
+        .accountsPartial({
+          maker: maker.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(
+          [mintX, mintZ].flatMap((mint) => [
+            readonly(mint),
+            writable(getAssociatedTokenAddressSync(mint, maker.publicKey)),
+            writable(vaultOf(seed, mint)),
+          ])
+        )
+        .signers([maker])
+        .rpc();
+
+    const takeBasket = (
+      seed: BN,
+      deposits: [anchor.web3.PublicKey, anchor.web3.PublicKey][]
+    ) =>
+      program.methods
+        .takeBasket()
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker: maker.publicKey,
+          basket: basketPda(seed),
+          treasury: treasury.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts([
+          ...deposits.flatMap(([mint, takerAta]) => [
+            readonly(mint),
+            writable(vaultOf(seed, mint)),
+            writable(takerAta),
+          ]),
+          ...[
+            [mintY, takerAtaY, makerAtaY],
+            [mintW, takerAtaW, makerAtaW],
+          ].flatMap(([mint, takerAta, makerAta]) => [
+            readonly(mint),
+            writable(takerAta),
+            writable(makerAta),
+            writable(getAssociatedTokenAddressSync(mint, treasury.publicKey)),
+          ]),
+        ])
+        .signers([taker])
+        .rpc();
+
+    it("Swaps every leg of a basket in one take", async () => {
+      const seed = new BN(1);
+      const basket = basketPda(seed);
+      const vaultX = vaultOf(seed, mintX);
+      const vaultZ = vaultOf(seed, mintZ);
+
+      await makeBasket(seed);
+
+      assert.equal(await balance(vaultX), 700);
+      assert.equal(await balance(vaultZ), 1_000);
+
+      const before = await Promise.all(
+        [takerAtaX, takerAtaZ, makerAtaY, makerAtaW].map(balance)
+      );
+
+      await takeBasket(seed, [
+        [mintX, takerAtaX],
+        [mintZ, takerAtaZ],
+      ]);
+
+      const after = await Promise.all(
+        [takerAtaX, takerAtaZ, makerAtaY, makerAtaW].map(balance)
+      );
+      assert.deepEqual(
+        after.map((amount, i) => amount - before[i]),
+        [700, 1_000, 2_000, 3_000]
+      );
+      assert.isNull(await connection.getAccountInfo(vaultX));
+      assert.isNull(await connection.getAccountInfo(vaultZ));
+      assert.isNull(await connection.getAccountInfo(basket));
+    });
+
+    it("Rejects accounts that do not match the basket's legs", async () => {
+      const seed = new BN(2);
+      const basket = basketPda(seed);
+
+      await makeBasket(seed);
+
+      try {
+        await takeBasket(seed, [
+          [mintZ, takerAtaZ],
+          [mintX, takerAtaX],
+        ]);
+        assert.fail("take_basket should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "InvalidTokenAccounts");
+      }
+
+      const before = await balance(makerAtaZ);
+
+      await program.methods
+        .refundBasket()
+        .accountsPartial({
+          maker: maker.publicKey,
+          basket,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(
+          [mintX, mintZ].flatMap((mint) => [
+            readonly(mint),
+            writable(vaultOf(seed, mint)),
+            writable(getAssociatedTokenAddressSync(mint, maker.publicKey)),
+          ])
+        )
+        .signers([maker])
+        .rpc();
+
+      assert.equal(await balance(makerAtaZ), before + 1_000);
+      assert.isNull(await connection.getAccountInfo(basket));
+    });
+
+    it("Rejects duplicate mints in a basket", async () => {
+      try {
+        await program.methods
+          .makeBasket(
+            new BN(3),
+            [leg(mintX, 700)],
+            [leg(mintY, 1_000), leg(mintY, 2_000)],
+            null,
+            null
+          )
+          .accountsPartial({
+            maker: maker.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .remainingAccounts([
+            readonly(mintX),
+            writable(makerAtaX),
+            writable(vaultOf(new BN(3), mintX)),
+          ])
+          .signers([maker])
+          .rpc();
+        assert.fail("make_basket should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "InvalidBasket");
+      }
+    });
+  });
+
+  describe("token-2022", () => {
+    const createMint2022 = async (
+      extensions: ExtensionType[],
+      initExtensions: (
+        mint: anchor.web3.PublicKey
+      ) => anchor.web3.TransactionInstruction[]
+    ) => {
+      const mint = anchor.web3.Keypair.generate();
+      const space = getMintLen(extensions);
+      const lamports = await connection.getMinimumBalanceForRentExemption(
+        space
+      );
+
+      const tx = new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          newAccountPubkey: mint.publicKey,
+          space,
+          lamports,
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        ...initExtensions(mint.publicKey),
+        createInitializeMintInstruction(
+          mint.publicKey,
+          6,
+          payer.publicKey,
+          null,
+          TOKEN_2022_PROGRAM_ID
+        )
+      );
+      await provider.sendAndConfirm(tx, [mint]);
+
+      return mint.publicKey;
+    };
+
+    // 1% transfer fee, effectively uncapped.
+    const createFeeMint = () =>
+      createMint2022([ExtensionType.TransferFeeConfig], (mint) => [
+        createInitializeTransferFeeConfigInstruction(
+          mint,
+          payer.publicKey,
+          payer.publicKey,
+          100,
+          BigInt(1_000_000_000),
+          TOKEN_2022_PROGRAM_ID
+        ),
+      ]);
+
+    const fund2022 = async (
+      mint: anchor.web3.PublicKey,
+      owner: anchor.web3.PublicKey,
+      amount: number
+    ) => {
+      const ata = await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        mint,
+        owner,
+        false,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      if (amount > 0) {
+        await mintTo(
+          connection,
+          payer,
+          mint,
+          ata.address,
+          payer,
+          amount,
+          [],
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+        );
+      }
+      return ata.address;
+    };
+
+    const balance2022 = async (ata: anchor.web3.PublicKey) =>
+      Number(
+        (await getAccount(connection, ata, undefined, TOKEN_2022_PROGRAM_ID))
+          .amount
+      );
+
+    it("Settles an arbitrated payment in a transfer-fee mint", async () => {
+      const feeMint = await createFeeMint();
+      await fund2022(feeMint, maker.publicKey, 1_000_000);
+
+      const seed = new BN(4);
+      const [arbitration] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("arbitration"),
+          maker.publicKey.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      await program.methods
+        .makeArbitrated(
+          seed,
+          new BN(100_000),
+          taker.publicKey,
+          anchor.web3.Keypair.generate().publicKey
+        )
+        .accountsPartial({
+          payer: maker.publicKey,
+          mint: feeMint,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+      // 1% is withheld on the way in.
+      const arbitrationAccount = await program.account.arbitration.fetch(
+        arbitration
+      );
+      assert.equal(arbitrationAccount.amount.toNumber(), 99_000);
+
+      await program.methods
+        .release()
+        .accountsPartial({
+          authority: maker.publicKey,
+          payer: maker.publicKey,
+          payee: taker.publicKey,
+          mint: feeMint,
+          arbitration,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+      // And another 1% on the way out, harvested before the vault closes.
+      const takerAta = getAssociatedTokenAddressSync(
+        feeMint,
+        taker.publicKey,
+        false,
+        TOKEN_2022_PROGRAM_ID
+      );
+      assert.equal(await balance2022(takerAta), 98_010);
+      assert.isNull(await connection.getAccountInfo(arbitration));
+    });
+
+    it("Cancels a milestone escrow in a transfer-fee mint", async () => {
+      const feeMint = await createFeeMint();
+      const makerAta = await fund2022(feeMint, maker.publicKey, 1_000_000);
+
+      const seed = new BN(3);
+      const [milestoneEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("milestones"),
+          maker.publicKey.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      await program.methods
+        .makeMilestones(seed, taker.publicKey, [
+          { amount: new BN(60_000), unlockAt: null },
+          { amount: new BN(40_000), unlockAt: null },
+        ])
+        .accountsPartial({
+          payer: maker.publicKey,
+          mint: feeMint,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+      // The 1% withheld on the way in comes out of the last milestone.
+      const { milestones } = await program.account.milestoneEscrow.fetch(
+        milestoneEscrow
+      );
+      assert.equal(milestones[0].amount.toNumber(), 60_000);
+      assert.equal(milestones[1].amount.toNumber(), 39_000);
+
+      await program.methods
+        .cancelMilestones()
+        .accountsPartial({
+          payer: maker.publicKey,
+          mint: feeMint,
+          milestoneEscrow,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([maker])
+        .rpc();
+
+      // Another 1% on the way back, harvested before the vault closes.
+      assert.equal(await balance2022(makerAta), 900_000 + 98_010);
+      assert.isNull(await connection.getAccountInfo(milestoneEscrow));
+    });
+
+    it("Grosses up transfer fees so the maker gets the full ask", async () => {
+      const feeMintX = await createFeeMint();
+      const feeMintY = await createFeeMint();
+      await createMarket(feeMintX, feeMintY);
+
+      await fund2022(feeMintX, maker.publicKey, 1_000_000);
+      await fund2022(feeMintY, taker.publicKey, 1_000_000);
+      const takerFeeAtaX = await fund2022(feeMintX, taker.publicKey, 0);
+      const makerFeeAtaY = getAssociatedTokenAddressSync(
+        feeMintY,
+        maker.publicKey,
+        false,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      const seed = new BN(16);
+      const escrow = escrowPda(seed);
+      const vault = getAssociatedTokenAddressSync(
+        feeMintX,
+        escrow,
+        true,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      await program.methods
+        .make(seed, new BN(100_000), new BN(50_000), makeOptions({}))
+        .accountsPartial({
+          maker: maker.publicKey,
+          mintX: feeMintX,
+          mintY: feeMintY,
+          tokenProgramX: TOKEN_2022_PROGRAM_ID,
+          tokenProgramY: TOKEN_2022_PROGRAM_ID,
+          ...noReceipt,
+        })
+        .signers([maker])
+        .rpc();
+
+      // 1% of the deposit is withheld on the way into the vault.
+      const escrowAccount = await program.account.escrow.fetch(escrow);
+      assert.equal(escrowAccount.depositAmount.toNumber(), 99_000);
+      assert.equal(await balance2022(vault), 99_000);
+
+      await program.methods
+        .take(new BN(50_000), null)
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker: maker.publicKey,
+          mintX: feeMintX,
+          mintY: feeMintY,
+          escrow,
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_2022_PROGRAM_ID,
+          tokenProgramY: TOKEN_2022_PROGRAM_ID,
+          proceeds: null,
+        })
+        .signers([taker])
+        .rpc();
+
+      // The taker grosses up the payment, then pays the fee on the payout.
+      assert.equal(await balance2022(makerFeeAtaY), 50_000);
+      assert.equal(await balance2022(takerFeeAtaX), 98_010);
+      assert.isNull(await connection.getAccountInfo(vault));
+      assert.isNull(await connection.getAccountInfo(escrow));
+    });
+
+    it("Swaps a token-2022 mint for a classic SPL mint", async () => {
+      const feeMintX = await createFeeMint();
+      await createMarket(feeMintX, mintY);
+
+      await fund2022(feeMintX, maker.publicKey, 1_000_000);
+      const takerFeeAtaX = await fund2022(feeMintX, taker.publicKey, 0);
+
+      const seed = new BN(44);
+      const escrow = escrowPda(seed);
+      const vault = getAssociatedTokenAddressSync(
+        feeMintX,
+        escrow,
+        true,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      await program.methods
+        .make(seed, new BN(100_000), new BN(50_000), makeOptions({}))
+        .accountsPartial({
+          maker: maker.publicKey,
+          mintX: feeMintX,
+          mintY,
+          tokenProgramX: TOKEN_2022_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          ...noReceipt,
+        })
+        .signers([maker])
+        .rpc();
+
+      assert.equal(await balance2022(vault), 99_000);
+
+      const makerYBefore = await balance(makerAtaY);
+      const takerYBefore = await balance(takerAtaY);
+
+      await program.methods
+        .take(new BN(50_000), null)
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker: maker.publicKey,
+          mintX: feeMintX,
+          mintY,
+          escrow,
+          treasury: treasury.publicKey,
+          tokenProgramX: TOKEN_2022_PROGRAM_ID,
+          tokenProgramY: TOKEN_PROGRAM_ID,
+          proceeds: null,
+        })
+        .signers([taker])
+        .rpc();
+
+      // Only the token-2022 leg pays a transfer fee.
+      assert.equal((await balance(makerAtaY)) - makerYBefore, 50_000);
+      assert.equal(takerYBefore - (await balance(takerAtaY)), 50_000);
+      assert.equal(await balance2022(takerFeeAtaX), 98_010);
+      assert.isNull(await connection.getAccountInfo(escrow));
+    });
+
+    it("Rejects non-transferable mints", async () => {
+      const lockedMint = await createMint2022(
+        [ExtensionType.NonTransferable],
+        (mint) => [
+          createInitializeNonTransferableMintInstruction(
+            mint,
+            TOKEN_2022_PROGRAM_ID
+          ),
+        ]
+      );
+      const tradableMint = await createFeeMint();
+      await createMarket(tradableMint, lockedMint);
+
+      await fund2022(tradableMint, maker.publicKey, 1_000_000);
+
+      try {
+        await program.methods
+          .make(new BN(17), new BN(1_000), new BN(1_000), makeOptions({}))
+          .accountsPartial({
+            maker: maker.publicKey,
+            mintX: tradableMint,
+            mintY: lockedMint,
+            tokenProgramX: TOKEN_2022_PROGRAM_ID,
+            tokenProgramY: TOKEN_2022_PROGRAM_ID,
+            ...noReceipt,
+          })
+          .signers([maker])
+          .rpc();
+        assert.fail("make should have failed");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "UnsupportedMintExtension");
+      }
+    });
+  });
+});
@@ -1,4 +1,13 @@
 use anchor_lang::prelude::*;
+use counter::{
+    cpi::{
+        accounts::{Initialize, Update},
+        initialize, update,
+    },
+    instructions::CounterAction,
+    program::Counter as CounterProgram,
+    state::Counter,
+};
 
 declare_id!("C6B9nB2B9pr9LsLGvHfBE6SgKtbs2BrHw1UkXUdmbseT");
 
@@ -10,7 +19,63 @@ pub mod hello_solana {
         msg!("Hello, Solana from program: {:?}", ctx.program_id);
         Ok(())
     }
+
+    pub fn hello_and_count(ctx: Context<HelloAndCount>) -> Result<u8> {
+        msg!("Hello, Solana from program: {:?}", ctx.program_id);
+        ctx.accounts.count(&ctx.bumps)
+    }
 }
 
 #[derive(Accounts)]
 pub struct Hello {}
+
+#[derive(Accounts)]
+pub struct HelloAndCount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: PDA of this program used as the counter account address. It is
+    /// created and owned by the counter program, which validates it on update.
+    #[account(mut, seeds = [b"counter"], bump)]
+    pub counter: UncheckedAccount<'info>,
+
+    pub counter_program: Program<'info, CounterProgram>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> HelloAndCount<'info> {
+    pub fn count(&mut self, bumps: &HelloAndCountBumps) -> Result<u8> {
+        // The counter account lives at our PDA, so only this program can sign
+        // for its creation inside the counter program.
+        if self.counter.data_is_empty() {
+            let signer_seeds: &[&[&[u8]]] = &[&[b"counter", &[bumps.counter]]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.counter_program.to_account_info(),
+                Initialize {
+                    payer: self.payer.to_account_info(),
+                    counter: self.counter.to_account_info(),
+                    system_program: self.system_program.to_account_info(),
+                },
+                signer_seeds,
+            );
+
+            initialize(cpi_ctx)?;
+        }
+
+        let cpi_ctx = CpiContext::new(
+            self.counter_program.to_account_info(),
+            Update {
+                counter: self.counter.to_account_info(),
+            },
+        );
+
+        update(cpi_ctx, CounterAction::Increment)?;
+
+        let data = self.counter.try_borrow_data()?;
+        let count = Counter::try_deserialize(&mut &data[..])?.count;
+
+        msg!("Counter is now {}", count);
+        Ok(count)
+    }
+}
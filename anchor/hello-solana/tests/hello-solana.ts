@@ -1,6 +1,13 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { HelloSolana } from "../target/types/hello_solana";
+import { assert } from "chai";
+
+// The counter program is loaded at genesis from `../counter/target/deploy`,
+// so run `anchor build` in `anchor/counter` before these tests.
+const COUNTER_PROGRAM_ID = new anchor.web3.PublicKey(
+  "hbJ8Kmhb8EZ2nHZ7nFhjDnKaAcWATycCmHr9WY4DaEo"
+);
 
 describe("hello-solana", () => {
   const provider = anchor.AnchorProvider.env();
@@ -8,9 +15,52 @@ describe("hello-solana", () => {
 
   const program = anchor.workspace.HelloSolana as Program<HelloSolana>;
 
+  const [counter] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("counter")],
+    program.programId
+  );
+
+  const fetchCount = async () => {
+    const account = await provider.connection.getAccountInfo(counter);
+
+    assert.isTrue(account.owner.equals(COUNTER_PROGRAM_ID));
+
+    // 8 byte discriminator followed by `count: u8`
+    return account.data[8];
+  };
+
+  // Sends `hello_and_count` and returns the count it reports as return data.
+  const helloAndCount = async () => {
+    const signature = await program.methods
+      .helloAndCount()
+      .accounts({
+        payer: provider.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [data] = tx.meta.returnData.data;
+
+    // `u8` return value
+    return Buffer.from(data, "base64")[0];
+  };
+
   it("Says hello solana", async () => {
     const tx = await program.methods.hello().rpc();
 
     console.log("Transaction signature:", tx);
   });
+
+  it("Says hello and creates the counter through CPI", async () => {
+    assert.equal(await helloAndCount(), 1);
+    assert.equal(await fetchCount(), 1);
+  });
+
+  it("Says hello and increments the existing counter", async () => {
+    assert.equal(await helloAndCount(), 2);
+    assert.equal(await fetchCount(), 2);
+  });
 });